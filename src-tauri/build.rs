@@ -1,3 +1,10 @@
 fn main() {
-    tauri_build::build()
+    // 应用自己的命令也要在 capabilities 中授权，窗口只能调用其中列出的命令
+    let manifest = tauri_build::AppManifest::new().commands(&[
+        "get_history",
+        "get_health_report",
+        "export_health_report",
+    ]);
+    tauri_build::try_build(tauri_build::Attributes::new().app_manifest(manifest))
+        .expect("Failed to run tauri-build");
 }
@@ -0,0 +1,240 @@
+use std::str::FromStr;
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::battery_source::{BatteryReading, ChargeState};
+
+/// 多块电池的汇总方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Aggregation {
+    /// 按能量加权合并为一个电量
+    #[default]
+    Combined,
+    /// 分别显示每块电池
+    PerBattery,
+    /// 只显示第一块电池
+    Primary,
+}
+
+impl Aggregation {
+    pub const ALL: [Aggregation; 3] = [Aggregation::Combined, Aggregation::PerBattery, Aggregation::Primary];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Aggregation::Combined => "combined",
+            Aggregation::PerBattery => "per-battery",
+            Aggregation::Primary => "primary",
+        }
+    }
+
+    /// 托盘菜单中显示的名称
+    pub fn label(&self) -> &'static str {
+        match self {
+            Aggregation::Combined => "Combined",
+            Aggregation::PerBattery => "Per battery",
+            Aggregation::Primary => "Primary only",
+        }
+    }
+
+    /// 用于图标与提示标题的代表读数，PerBattery 模式下同样使用合并值
+    pub fn headline(&self, batteries: &[BatteryReading]) -> Option<BatteryReading> {
+        match self {
+            Aggregation::Combined | Aggregation::PerBattery => combine(batteries),
+            Aggregation::Primary => batteries.first().cloned(),
+        }
+    }
+}
+
+impl FromStr for Aggregation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Aggregation::ALL.into_iter().find(|a| a.as_str() == s) {
+            Some(aggregation) => Ok(aggregation),
+            None => bail!("Unknown aggregation: {}", s),
+        }
+    }
+}
+
+/// 合并多块电池的读数。所有电池都有能量数据时按能量加权，否则取电量平均值
+pub fn combine(batteries: &[BatteryReading]) -> Option<BatteryReading> {
+    match batteries {
+        [] => None,
+        [single] => Some(single.clone()),
+        _ => {
+            let energy: Option<f32> = batteries.iter().map(|b| b.energy).sum();
+            let energy_full: Option<f32> = batteries.iter().map(|b| b.energy_full).sum();
+
+            let percentage = match (energy, energy_full) {
+                (Some(now), Some(full)) if full > 0.0 => (now / full * 100.0).round() as u32,
+                _ => {
+                    let total: u32 = batteries.iter().map(|b| b.percentage).sum();
+                    (total as f32 / batteries.len() as f32).round() as u32
+                }
+            };
+
+            let state = combine_state(batteries);
+            Some(BatteryReading {
+                name: "Combined".to_string(),
+                model: None,
+                percentage: percentage.min(100),
+                state,
+                energy,
+                energy_full,
+                energy_rate: combine_rate(batteries, state),
+                voltage: None,
+                temperature: batteries
+                    .iter()
+                    .filter_map(|b| b.temperature)
+                    .reduce(f32::max),
+                // 各电池报告的时间无法直接相加，交给估算器根据总能量与功率计算
+                time_to_empty: None,
+                time_to_full: None,
+            })
+        }
+    }
+}
+
+/// 合并后的功率（W）。各电池的功率只有大小，按状态加上方向：充电为正、放电为负，
+/// 一块充电一块放电时（如双电池的 ThinkPad）互相抵消。净功率与合并后的状态方向相反时为 0
+fn combine_rate(batteries: &[BatteryReading], state: ChargeState) -> Option<f32> {
+    let net: f32 = batteries
+        .iter()
+        .map(|b| {
+            let rate = b.energy_rate?.abs();
+            Some(match b.state {
+                ChargeState::Charging => rate,
+                ChargeState::Discharging => -rate,
+                _ => 0.0,
+            })
+        })
+        .sum::<Option<f32>>()?;
+    Some(match state {
+        ChargeState::Charging => net.max(0.0),
+        ChargeState::Discharging => (-net).max(0.0),
+        _ => net.abs(),
+    })
+}
+
+/// 任一电池在充电即视为充电，其次是放电；全部充满或耗尽时才报告对应状态，
+/// 接通电源且各电池都已充满或暂停充电时视为未充电
+fn combine_state(batteries: &[BatteryReading]) -> ChargeState {
+    let any = |state| batteries.iter().any(|b| b.state == state);
+    let all = |state| batteries.iter().all(|b| b.state == state);
+
+    if any(ChargeState::Charging) {
+        ChargeState::Charging
+    } else if any(ChargeState::Discharging) {
+        ChargeState::Discharging
+    } else if all(ChargeState::Full) {
+        ChargeState::Full
+    } else if all(ChargeState::Empty) {
+        ChargeState::Empty
+    } else if batteries.iter().all(|b| b.state.is_plugged_in()) {
+        ChargeState::NotCharging
+    } else {
+        ChargeState::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_rate(name: &str, state: ChargeState, energy_rate: f32) -> BatteryReading {
+        BatteryReading {
+            energy_rate: Some(energy_rate),
+            ..BatteryReading::new(name, 50, state)
+        }
+    }
+
+    fn battery(name: &str, percentage: u32, state: ChargeState, energy: Option<(f32, f32)>) -> BatteryReading {
+        BatteryReading {
+            energy: energy.map(|(now, _)| now),
+            energy_full: energy.map(|(_, full)| full),
+            ..BatteryReading::new(name, percentage, state)
+        }
+    }
+
+    #[test]
+    fn combine_weights_by_energy() {
+        let batteries = [
+            battery("BAT0", 90, ChargeState::Discharging, Some((18.0, 20.0))),
+            battery("BAT1", 10, ChargeState::Discharging, Some((6.0, 60.0))),
+        ];
+        let combined = combine(&batteries).unwrap();
+        assert_eq!(combined.name, "Combined");
+        assert_eq!(combined.percentage, 30);
+        assert_eq!(combined.energy, Some(24.0));
+        assert_eq!(combined.energy_full, Some(80.0));
+        assert_eq!(combined.time_to_empty, None);
+    }
+
+    #[test]
+    fn combine_averages_without_energy() {
+        let batteries = [
+            battery("BAT0", 90, ChargeState::Discharging, Some((18.0, 20.0))),
+            battery("BAT1", 15, ChargeState::Discharging, None),
+        ];
+        assert_eq!(combine(&batteries).unwrap().percentage, 53);
+        assert_eq!(combine(&batteries).unwrap().energy, None);
+    }
+
+    #[test]
+    fn combine_passes_single_battery_through() {
+        assert_eq!(combine(&[]), None);
+        let single = battery("BAT0", 42, ChargeState::Charging, Some((21.0, 50.0)));
+        assert_eq!(combine(std::slice::from_ref(&single)), Some(single));
+    }
+
+    #[test]
+    fn combine_nets_charging_against_discharging() {
+        use ChargeState::*;
+        let cases = [
+            (vec![with_rate("BAT0", Discharging, 8.0), with_rate("BAT1", Discharging, 4.0)], Some(12.0)),
+            // 一块充电一块放电：净充电 6 W
+            (vec![with_rate("BAT0", Charging, 10.0), with_rate("BAT1", Discharging, 4.0)], Some(6.0)),
+            // 放电多于充电时净功率与合并后的充电状态方向相反
+            (vec![with_rate("BAT0", Charging, 2.0), with_rate("BAT1", Discharging, 9.0)], Some(0.0)),
+            (vec![with_rate("BAT0", Full, 0.0), with_rate("BAT1", Discharging, 5.0)], Some(5.0)),
+            (vec![with_rate("BAT0", Discharging, 5.0), BatteryReading::new("BAT1", 50, Discharging)], None),
+        ];
+        for (batteries, expected) in cases {
+            assert_eq!(combine(&batteries).unwrap().energy_rate, expected, "{:?}", batteries);
+        }
+    }
+
+    #[test]
+    fn combine_state_prefers_activity() {
+        use ChargeState::*;
+        let cases: [(&[ChargeState], ChargeState); 8] = [
+            (&[Charging, Discharging], Charging),
+            (&[Discharging, Full], Discharging),
+            (&[Full, Full], Full),
+            (&[Empty, Empty], Empty),
+            (&[Full, NotCharging], NotCharging),
+            (&[Full, Empty], Unknown),
+            (&[Unknown, Full], Unknown),
+            (&[NotCharging, NotCharging], NotCharging),
+        ];
+        for (states, expected) in cases {
+            let batteries: Vec<BatteryReading> = states
+                .iter()
+                .enumerate()
+                .map(|(i, &state)| BatteryReading::new(format!("BAT{}", i), 50, state))
+                .collect();
+            assert_eq!(combine_state(&batteries), expected, "{:?}", states);
+        }
+    }
+
+    #[test]
+    fn primary_uses_first_battery() {
+        let batteries = [
+            BatteryReading::new("BAT0", 70, ChargeState::Discharging),
+            BatteryReading::new("BAT1", 20, ChargeState::Discharging),
+        ];
+        assert_eq!(Aggregation::Primary.headline(&batteries).unwrap().name, "BAT0");
+        assert_eq!(Aggregation::PerBattery.headline(&batteries).unwrap().percentage, 45);
+    }
+}
@@ -0,0 +1,193 @@
+use image::{Rgba, RgbaImage};
+use imageproc::{
+    drawing::{draw_filled_rect_mut, draw_polygon_mut},
+    point::Point,
+    rect::Rect,
+};
+
+use crate::icon_style::Color;
+
+/// 电池图形各部分的颜色
+pub struct GlyphPaint {
+    /// 外壳
+    pub frame: Color,
+    /// 电量条
+    pub fill: Color,
+    /// 外壳内部的空白，通常为背景色或透明
+    pub empty: Color,
+    /// 闪电等状态标记
+    pub bolt: Color,
+}
+
+/// 叠加在电池中间的状态标记
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphMark {
+    /// 闪电：正在充电
+    Bolt,
+    /// 暂停符号：接通电源但未充电
+    Pause,
+}
+
+/// 闪电形状的顶点，坐标为相对电池内部区域的比例
+const BOLT: [(f32, f32); 6] = [
+    (0.58, 0.0),
+    (0.22, 0.56),
+    (0.46, 0.56),
+    (0.40, 1.0),
+    (0.78, 0.40),
+    (0.54, 0.40),
+];
+
+/// 在正方形画布上绘制电池外形：外壳、按电量比例填充的电量条，可选叠加状态标记。
+/// 多块电池时将电量条按行等分，每行对应一块电池。返回电池内部区域，供叠加文字使用
+pub fn draw_battery_glyph(img: &mut RgbaImage, levels: &[u32], mark: Option<GlyphMark>, paint: &GlyphPaint) -> Rect {
+    let size = img.width().min(img.height()) as f32;
+    let px = |ratio: f32| (size * ratio).round() as i32;
+    let border = px(0.05).max(1);
+
+    // 外壳与右侧的正极凸起
+    let body = Rect::at(px(0.03), px(0.22)).of_size(px(0.86) as u32, px(0.56) as u32);
+    let nub = Rect::at(body.right() + 1, px(0.38)).of_size(px(0.08).max(1) as u32, px(0.24) as u32);
+    draw_filled_rect_mut(img, body, Rgba(paint.frame.0));
+    draw_filled_rect_mut(img, nub, Rgba(paint.frame.0));
+
+    let inside = shrink(body, border);
+    draw_filled_rect_mut(img, inside, Rgba(paint.empty.0));
+
+    // 电量条与外壳之间留出一圈空隙
+    let bars = shrink(inside, (border / 2).max(1));
+    let rows = levels.len().max(1) as u32;
+    let row_height = (bars.height() / rows).max(1);
+    for (i, &level) in levels.iter().enumerate() {
+        let width = (bars.width() as f32 * level.min(100) as f32 / 100.0).round() as u32;
+        if width == 0 {
+            continue;
+        }
+        let top = bars.top() + (i as u32 * row_height) as i32;
+        let gap = if i + 1 < levels.len() { 1 } else { 0 };
+        let height = row_height.saturating_sub(gap).max(1);
+        draw_filled_rect_mut(img, Rect::at(bars.left(), top).of_size(width, height), Rgba(paint.fill.0));
+    }
+
+    match mark {
+        Some(GlyphMark::Bolt) => {
+            let points: Vec<Point<i32>> = BOLT
+                .iter()
+                .map(|&(x, y)| {
+                    // 按最后一个像素而不是边长缩放，比例为 1.0 的顶点才不会画到外壳上
+                    Point::new(
+                        inside.left() + ((inside.width() - 1) as f32 * x).round() as i32,
+                        inside.top() + ((inside.height() - 1) as f32 * y).round() as i32,
+                    )
+                })
+                .collect();
+            draw_polygon_mut(img, &points, Rgba(paint.bolt.0));
+        }
+        Some(GlyphMark::Pause) => {
+            // 两条竖线，宽度与间隔各占内部区域宽度的 12%
+            let bar_width = ((inside.width() as f32 * 0.12).round() as u32).max(1);
+            let bar_height = ((inside.height() as f32 * 0.7).round() as u32).max(1);
+            let top = inside.top() + (inside.height() - bar_height) as i32 / 2;
+            let center = inside.left() + inside.width() as i32 / 2;
+            for left in [center - bar_width as i32 * 3 / 2, center + bar_width as i32 / 2] {
+                draw_filled_rect_mut(img, Rect::at(left, top).of_size(bar_width, bar_height), Rgba(paint.bolt.0));
+            }
+        }
+        None => {}
+    }
+
+    inside
+}
+
+/// 向内收缩矩形
+fn shrink(rect: Rect, by: i32) -> Rect {
+    let width = (rect.width() as i32 - by * 2).max(1) as u32;
+    let height = (rect.height() as i32 - by * 2).max(1) as u32;
+    Rect::at(rect.left() + by, rect.top() + by).of_size(width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use imageproc::rect::Region;
+    use super::*;
+
+    const PAINT: GlyphPaint = GlyphPaint {
+        frame: Color([0, 0, 0, 255]),
+        fill: Color([0, 200, 0, 255]),
+        empty: Color([255, 255, 255, 255]),
+        bolt: Color([255, 200, 0, 255]),
+    };
+
+    fn draw(levels: &[u32], mark: Option<GlyphMark>) -> (RgbaImage, Rect) {
+        let mut img = RgbaImage::new(32, 32);
+        let inside = draw_battery_glyph(&mut img, levels, mark, &PAINT);
+        (img, inside)
+    }
+
+    /// 某一行中指定颜色的像素数
+    fn count_in_row(img: &RgbaImage, y: u32, color: Color) -> usize {
+        (0..img.width()).filter(|&x| img.get_pixel(x, y).0 == color.0).count()
+    }
+
+    fn count(img: &RgbaImage, color: Color) -> usize {
+        img.pixels().filter(|pixel| pixel.0 == color.0).count()
+    }
+
+    #[test]
+    fn inside_lies_within_the_canvas() {
+        for size in [16, 32, 64] {
+            let mut img = RgbaImage::new(size, size);
+            let inside = draw_battery_glyph(&mut img, &[50], None, &PAINT);
+            assert!(inside.left() > 0 && inside.top() > 0, "{}", size);
+            assert!(inside.right() < size as i32 && inside.bottom() < size as i32, "{}", size);
+            // 外壳与右侧凸起之外保持透明
+            for (x, y) in [(0, 0), (size - 1, 0), (0, size - 1), (size - 1, size - 1)] {
+                assert_eq!(img.get_pixel(x, y).0, [0, 0, 0, 0], "{} at {},{}", size, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn fill_width_follows_level() {
+        let (_, inside) = draw(&[100], None);
+        let row = (inside.top() + inside.bottom()) as u32 / 2;
+        let widths: Vec<usize> = [0, 25, 50, 100]
+            .iter()
+            .map(|&level| count_in_row(&draw(&[level], None).0, row, PAINT.fill))
+            .collect();
+        assert_eq!(widths[0], 0);
+        assert!(widths[1] < widths[2] && widths[2] < widths[3], "{:?}", widths);
+        assert!(widths[3] < inside.width() as usize);
+        assert!(widths[2].abs_diff(widths[3] / 2) <= 1, "{:?}", widths);
+        // 超过 100 按 100 绘制
+        assert_eq!(count(&draw(&[150], None).0, PAINT.fill), count(&draw(&[100], None).0, PAINT.fill));
+    }
+
+    #[test]
+    fn splits_rows_per_battery() {
+        let (img, inside) = draw(&[100, 0], None);
+        let middle = (inside.top() + inside.bottom()) as u32 / 2;
+        let filled_rows: Vec<u32> = (0..img.height())
+            .filter(|&y| count_in_row(&img, y, PAINT.fill) > 0)
+            .collect();
+        assert!(!filled_rows.is_empty());
+        assert!(filled_rows.iter().all(|&y| y < middle), "{:?}", filled_rows);
+
+        let (img, _) = draw(&[100, 100], None);
+        assert!((0..img.height()).any(|y| y > middle && count_in_row(&img, y, PAINT.fill) > 0));
+    }
+
+    #[test]
+    fn draws_marks_inside_the_battery() {
+        assert_eq!(count(&draw(&[50], None).0, PAINT.bolt), 0);
+        for mark in [GlyphMark::Bolt, GlyphMark::Pause] {
+            let (img, inside) = draw(&[50], Some(mark));
+            assert!(count(&img, PAINT.bolt) > 0, "{:?}", mark);
+            for (x, y, pixel) in img.enumerate_pixels() {
+                if pixel.0 == PAINT.bolt.0 {
+                    assert!(inside.contains(x as i32, y as i32), "{:?} at {},{}", mark, x, y);
+                }
+            }
+        }
+    }
+}
@@ -1,30 +1,167 @@
-use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
-use anyhow::{ensure, Context, Result};
-use image::{Rgba, RgbaImage};
-use imageproc::drawing::draw_text_mut;
-use std::io::Cursor;
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use anyhow::{ensure, Result};
+use image::{imageops, Rgba, RgbaImage};
+use imageproc::{drawing::draw_text_mut, rect::Rect};
+use std::{collections::VecDeque, sync::Mutex};
 use tauri::image::Image;
 
+use crate::{
+    battery_glyph::{draw_battery_glyph, GlyphMark, GlyphPaint},
+    battery_source::ChargeState,
+    fonts::BundledFont,
+    icon_style::{Color, IconMode, IconStyle, Theme},
+    template::TemplateContext,
+};
+
+/// 缓存图标的键：同样的电量、文字、配色和尺寸总是得到同样的图标
+#[derive(PartialEq, Eq, Hash)]
+struct IconKey {
+    batteries: Vec<(u32, ChargeState)>,
+    text: String,
+    theme: Theme,
+    size: u32,
+}
+
+/// 最近使用过的图标。电量通常只在相邻的几个值之间变化，几十个就能覆盖，
+/// 多块电池的各种组合也不会让缓存无限增长
+struct IconCache<V> {
+    /// 按最近使用排序，最前面的最新
+    entries: VecDeque<(IconKey, V)>,
+    capacity: usize,
+}
+
+impl<V: Clone> IconCache<V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// 命中时把该项移到最前面
+    fn get(&mut self, key: &IconKey) -> Option<V> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(index)?;
+        let value = entry.1.clone();
+        self.entries.push_front(entry);
+        Some(value)
+    }
+
+    /// 已满时丢弃最久没有使用的一项
+    fn insert(&mut self, key: IconKey, value: V) {
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_back();
+        }
+        self.entries.push_front((key, value));
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
 /// 电池图标生成器类
 pub struct BatteryIconGenerator {
-    font: FontRef<'static>,
+    font: FontArc,
+    style: IconStyle,
+    theme: Theme,
+    size: u32,
+    /// 已生成的图标，样式变化时清空
+    cache: Mutex<IconCache<Image<'static>>>,
+}
+
+impl Default for BatteryIconGenerator {
+    fn default() -> Self {
+        Self {
+            font: BundledFont::ComicMono.load(),
+            style: IconStyle::default(),
+            theme: Theme::LIGHT,
+            size: Self::DEFAULT_SIZE,
+            cache: Mutex::new(IconCache::new(Self::CACHE_CAPACITY)),
+        }
+    }
 }
 
 impl BatteryIconGenerator {
-    const SIZE: u32 = 64;
-    pub fn new() -> Result<Self> {
-        let font = FontRef::try_from_slice(include_bytes!("../assets/ComicMono.ttf"))
-            .context("Failed to load font")?;
-        Ok(Self { font })
+    /// 托盘常用的图标尺寸
+    pub const STANDARD_SIZES: [u32; 6] = [16, 20, 24, 32, 48, 64];
+    const DEFAULT_SIZE: u32 = 64;
+    /// 不超过此尺寸时按像素网格对齐并锐化文字
+    const CRISP_MAX_SIZE: u32 = 32;
+    /// 缓存的图标数，64px 的图标每个 16 KiB，全部缓存最多占用 512 KiB
+    const CACHE_CAPACITY: usize = 32;
+
+    /// 按屏幕缩放比例选择托盘图标尺寸：取不小于“平台逻辑尺寸 × 缩放比例”的最小标准尺寸
+    pub fn tray_size_for_scale(scale_factor: f64) -> u32 {
+        // Windows 托盘图标为 16 逻辑像素，macOS 菜单栏与常见 Linux 面板约为 22
+        let logical = if cfg!(windows) { 16.0 } else { 22.0 };
+        let physical = (logical * scale_factor).round() as u32;
+        Self::STANDARD_SIZES
+            .into_iter()
+            .find(|&size| size >= physical)
+            .unwrap_or(Self::DEFAULT_SIZE)
     }
 
-    /// 构造字符串，充电时添加星号，充满显示笑脸
-    fn build_text(&self, percentage: u32, charging: bool) -> String {
-        match (charging, percentage > 97) {
-            (true, true) => "^_^".to_string(),
-            (true, false) => format!("{percentage}*"),
-            (false, _) => format!("{percentage}"),
+    /// 更换图标尺寸（像素），下次生成时生效
+    pub fn set_size(&mut self, size: u32) {
+        self.size = size;
+    }
+
+    /// 描边宽度随图标尺寸缩放，64px 时为 2 像素
+    fn outline_width(&self) -> i32 {
+        (self.size / 32).max(1) as i32
+    }
+
+    /// 更换图标样式，下次生成时生效。字体加载失败时改用内置字体并返回错误
+    pub fn set_style(&mut self, style: IconStyle) -> Result<()> {
+        if self.style == style {
+            return Ok(());
         }
+        // 配色和尺寸已在缓存键中，其余样式变化时旧图标全部作废
+        self.cache.get_mut().unwrap().clear();
+        let font_changed = self.style.font != style.font;
+        self.style = style;
+
+        if font_changed {
+            let (font, error) = self.style.font.load_or_fallback();
+            self.font = font;
+            if let Some(e) = error {
+                return Err(e.context("Falling back to the bundled font"));
+            }
+        }
+        Ok(())
+    }
+
+    /// 更换配色，下次生成时生效
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// 按模板构造单块电池的文字，充满或接通电源且接近充满时显示笑脸；功率模式下显示功率
+    fn build_text(&self, context: &TemplateContext) -> String {
+        let style = &self.style;
+        if style.mode == IconMode::Power {
+            let text = style.power_text.render(context);
+            return if text.is_empty() { "--".to_string() } else { text };
+        }
+
+        let reading = context.reading;
+        let full = reading.state == ChargeState::Full
+            || (reading.state.is_plugged_in() && reading.percentage > style.full_threshold);
+        if full {
+            style.full_text.clone()
+        } else {
+            style.text.render(context)
+        }
+    }
+
+    /// 图标上的文字，多块电池时各电池的文字用斜杠分隔
+    pub fn icon_text(&self, contexts: &[TemplateContext]) -> String {
+        contexts
+            .iter()
+            .map(|context| self.build_text(context))
+            .collect::<Vec<_>>()
+            .join("/")
     }
 
     /// 计算字符串宽高
@@ -35,15 +172,15 @@ impl BatteryIconGenerator {
         (width, height)
     }
 
-    /// 计算字符串坐标，使其水平垂直居中
-    fn compute_position(&self, width: f32, height: f32) -> (i32, i32) {
-        let x = (BatteryIconGenerator::SIZE as f32 - width) / 2.0;
-        let y = (BatteryIconGenerator::SIZE as f32 - height) / 2.0;
+    /// 计算字符串坐标，使其在区域内水平垂直居中
+    fn compute_position(&self, area: Rect, width: f32, height: f32) -> (i32, i32) {
+        let x = area.left() as f32 + (area.width() as f32 - width) / 2.0;
+        let y = area.top() as f32 + (area.height() as f32 - height) / 2.0;
         (x as i32, y as i32)
     }
 
     /// 二分法寻找合适的宽度
-    fn find_scale_for_width(&self, text: &str) -> PxScale {
+    fn find_scale_for_width(&self, text: &str, max_width: f32) -> PxScale {
         const TOLERANCE: f32 = 0.1;
 
         let mut low = 1.0;
@@ -52,7 +189,7 @@ impl BatteryIconGenerator {
         while high - low > TOLERANCE {
             let mid = (low + high) / 2.0;
             let (width, _) = self.measure_text(text, PxScale::from(mid));
-            if width < BatteryIconGenerator::SIZE as f32 {
+            if width < max_width {
                 low = mid;
             } else {
                 high = mid;
@@ -62,32 +199,235 @@ impl BatteryIconGenerator {
         PxScale::from((low + high) / 2.0)
     }
 
-    /// 绘制图标并转为Tauri Image对象
-    fn render_icon(&self, x: i32, y: i32, scale: PxScale, text: &str) -> Result<Image<'static>> {
-        let mut img = RgbaImage::new(BatteryIconGenerator::SIZE, BatteryIconGenerator::SIZE);
-        draw_text_mut(&mut img, Rgba([0, 0, 0, 255]), x, y, scale, &self.font, &text);
+    /// 新建画布，有背景色时填充背景
+    fn new_canvas(&self) -> RgbaImage {
+        let size = self.size;
+        match self.theme.background {
+            Some(background) => RgbaImage::from_pixel(size, size, Rgba(background.0)),
+            None => RgbaImage::new(size, size),
+        }
+    }
 
-        let mut icon_data = Cursor::new(Vec::new());
-        img.write_to(&mut icon_data, image::ImageFormat::Ico)
-            .context("Failed to encode icon to ICO")?;
+    /// 在区域内居中绘制文字，字号取能放下文字的最大值
+    fn draw_text_in(&self, img: &mut RgbaImage, area: Rect, text: &str, foreground: Color) {
+        let crisp = self.size <= Self::CRISP_MAX_SIZE;
+        let outline_width = self.outline_width();
+        let margin = if self.theme.outline.is_some() { outline_width * 2 } else { 0 };
+        let width_scale = self.find_scale_for_width(text, (area.width() as i32 - margin) as f32);
+        // 按字体上升高度限制字号，避免文字超出区域的上下边界
+        let unit_ascent = self.font.as_scaled(PxScale::from(1.0)).ascent();
+        let height_scale = (area.height() as i32 - margin) as f32 / unit_ascent;
+        let mut scale = width_scale.y.min(height_scale);
+        if crisp {
+            // 小尺寸下取整数字号，让笔画落在像素网格上
+            scale = scale.floor().max(1.0);
+        }
+        let scale = PxScale::from(scale);
 
-        let icon_image = Image::from_bytes(&icon_data.into_inner())
-            .context("Failed to create Tauri image")?
-            .to_owned();
+        let (width, height) = self.measure_text(text, scale);
+        let (x, y) = self.compute_position(area, width, height);
 
-        Ok(icon_image)
+        // 文字先画在独立图层上，便于小尺寸时单独锐化
+        let mut layer = RgbaImage::new(img.width(), img.height());
+
+        // 描边：先在周围各方向偏移绘制描边色，再在中间绘制文字
+        if let Some(outline) = self.theme.outline {
+            let w = outline_width;
+            for (dx, dy) in [(-w, 0), (w, 0), (0, -w), (0, w), (-w, -w), (-w, w), (w, -w), (w, w)] {
+                draw_text_mut(&mut layer, Rgba(outline.0), x + dx, y + dy, scale, &self.font, text);
+            }
+        }
+        draw_text_mut(&mut layer, Rgba(foreground.0), x, y, scale, &self.font, text);
+
+        if crisp {
+            sharpen_alpha(&mut layer);
+        }
+        imageops::overlay(img, &layer, 0, 0);
+    }
+
+    /// 文字模式：整个图标只显示文字
+    fn render_text(&self, text: &str, foreground: Color) -> RgbaImage {
+        let mut img = self.new_canvas();
+        let size = self.size;
+        self.draw_text_in(&mut img, Rect::at(0, 0).of_size(size, size), text, foreground);
+        img
+    }
+
+    /// 电池外形模式：绘制电池外壳和电量条，可选叠加电量数字
+    fn render_glyph(&self, batteries: &[(u32, ChargeState)], text: &str, fill: Color) -> RgbaImage {
+        let mut img = self.new_canvas();
+        // 充满的电池即使报告的电量略低于 100% 也画满
+        let levels: Vec<u32> = batteries
+            .iter()
+            .map(|&(percentage, state)| if state == ChargeState::Full { 100 } else { percentage })
+            .collect();
+        let any = |state| batteries.iter().any(|&(_, s)| s == state);
+        let mark = if any(ChargeState::Charging) {
+            Some(GlyphMark::Bolt)
+        } else if any(ChargeState::NotCharging) {
+            Some(GlyphMark::Pause)
+        } else {
+            None
+        };
+        let paint = GlyphPaint {
+            frame: self.theme.foreground,
+            fill,
+            empty: self.theme.background.unwrap_or(Color::TRANSPARENT),
+            bolt: fill.contrasting(),
+        };
+        let inside = draw_battery_glyph(&mut img, &levels, mark, &paint);
+
+        // 状态标记占据中间位置，不再叠加文字；状态未知时总是显示问号
+        let lowest = levels.iter().copied().min().unwrap_or(0);
+        let text_color = if lowest > 50 { fill.contrasting() } else { self.theme.foreground };
+        if mark.is_none() && batteries.iter().all(|&(_, state)| state == ChargeState::Unknown) {
+            self.draw_text_in(&mut img, inside, "?", text_color);
+        } else if self.style.glyph_number && mark.is_none() && !text.is_empty() {
+            // 电量过半时数字大部分落在电量条上，改用与电量条对比的颜色
+            self.draw_text_in(&mut img, inside, text, text_color);
+        }
+        img
+    }
+
+    /// 转为Tauri Image对象，直接使用 RGBA 像素，无需编码
+    fn to_tauri_image(img: RgbaImage) -> Image<'static> {
+        let (width, height) = img.dimensions();
+        Image::new_owned(img.into_raw(), width, height)
+    }
+
+    /// 生成电池电量图标（默认 64x64，尺寸与配色由当前设置决定）。batteries 为各电池的电量与状态，
+    /// 电池外形模式下每块电池占一行电量条；text 由 icon_text 生成。生成过的图标直接从缓存返回
+    pub async fn generate_icon(&self, batteries: &[(u32, ChargeState)], text: &str) -> Result<Image<'static>> {
+        let key = IconKey {
+            batteries: batteries.to_vec(),
+            text: text.to_string(),
+            theme: self.theme,
+            size: self.size,
+        };
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(icon) = cache.get(&key) {
+            return Ok(icon);
+        }
+
+        let icon = Self::to_tauri_image(self.render_image(batteries, text)?);
+        cache.insert(key, icon.clone());
+        Ok(icon)
+    }
+
+    /// 不对应电池读数的状态图标，如没有电池时的 `AC`、出错时的 `!`。error 为 true 时使用低电量颜色
+    pub fn generate_status_icon(&self, text: &str, error: bool) -> Image<'static> {
+        let foreground = if error { self.style.colors.low_color } else { self.theme.foreground };
+        Self::to_tauri_image(self.render_text(text, foreground))
+    }
+
+    /// 按当前样式绘制图标，不经过缓存，供命令行导出图片使用
+    pub fn render_image(&self, batteries: &[(u32, ChargeState)], text: &str) -> Result<RgbaImage> {
+        ensure!(!batteries.is_empty(), "No battery to render");
+        ensure!(
+            batteries.iter().all(|(percentage, _)| (0..=100).contains(percentage)),
+            "Battery percentage must be between 0 and 100"
+        );
+        Ok(self.render(batteries, text))
+    }
+
+    /// 按电量着色时以最低的电量为准
+    fn render(&self, batteries: &[(u32, ChargeState)], text: &str) -> RgbaImage {
+        // 以电量最低的电池为准，电量相同时优先显示正在充电的状态
+        let (lowest, state) = batteries
+            .iter()
+            .copied()
+            .min_by_key(|&(percentage, state)| (percentage, state != ChargeState::Charging))
+            .unwrap_or((0, ChargeState::Unknown));
+        let foreground = self
+            .style
+            .colors
+            .color_for(lowest, state)
+            .unwrap_or(self.theme.foreground);
+
+        match self.style.mode {
+            IconMode::Text | IconMode::Power => self.render_text(text, foreground),
+            IconMode::Glyph => self.render_glyph(batteries, text, foreground),
+        }
+    }
+}
+
+/// 提高抗锯齿边缘的对比度：小尺寸下半透明像素占比大，文字会显得发虚
+fn sharpen_alpha(layer: &mut RgbaImage) {
+    for pixel in layer.pixels_mut() {
+        let alpha = pixel.0[3] as i32;
+        pixel.0[3] = ((alpha - 64) * 2).clamp(0, 255) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(percentage: u32) -> IconKey {
+        IconKey {
+            batteries: vec![(percentage, ChargeState::Discharging)],
+            text: percentage.to_string(),
+            theme: Theme::LIGHT,
+            size: 32,
+        }
+    }
+
+    fn keys<V>(cache: &IconCache<V>) -> Vec<u32> {
+        cache.entries.iter().map(|(key, _)| key.batteries[0].0).collect()
+    }
+
+    #[test]
+    fn cache_returns_hits_and_keeps_them() {
+        let mut cache = IconCache::new(3);
+        assert_eq!(cache.get(&key(1)), None);
+        for percentage in 1..=3 {
+            cache.insert(key(percentage), percentage * 10);
+        }
+        assert_eq!(cache.get(&key(1)), Some(10));
+        assert_eq!(keys(&cache), [1, 3, 2]);
+
+        // 最久没有使用的 2 被丢弃，刚命中的 1 保留
+        cache.insert(key(4), 40);
+        assert_eq!(keys(&cache), [4, 1, 3]);
+        assert_eq!(cache.get(&key(2)), None);
+        assert_eq!(cache.get(&key(1)), Some(10));
+
+        let other_theme = IconKey { theme: Theme::DARK, ..key(1) };
+        assert_eq!(cache.get(&other_theme), None);
+    }
+
+    #[test]
+    fn cache_stays_within_capacity() {
+        let mut cache = IconCache::new(BatteryIconGenerator::CACHE_CAPACITY);
+        for percentage in 0..=100 {
+            cache.insert(key(percentage), percentage);
+        }
+        assert_eq!(cache.entries.len(), BatteryIconGenerator::CACHE_CAPACITY);
+        assert_eq!(cache.get(&key(100)), Some(100));
+        assert_eq!(cache.get(&key(0)), None);
+        cache.clear();
+        assert!(cache.entries.is_empty());
     }
 
-    /// 生成电池电量图标（64x64，白底黑字）
-    pub async fn generate_icon(&self, percentage: u32, charging: bool) -> Result<Image<'static>> {
-        ensure!((0..=100).contains(&percentage), "Battery percentage must be between 0 and 100");
+    #[test]
+    fn generator_caches_rendered_icons() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let mut generator = BatteryIconGenerator::default();
+        let cached = |generator: &BatteryIconGenerator| keys(&generator.cache.lock().unwrap());
 
-        let text = self.build_text(percentage, charging);
-        let scale = self.find_scale_for_width(&text);
-        let (width, height) = self.measure_text(&text, scale);
-        let (x, y) = self.compute_position(width, height);
+        runtime.block_on(async {
+            let batteries = [(42, ChargeState::Discharging)];
+            generator.generate_icon(&batteries, "42").await.unwrap();
+            generator.generate_icon(&batteries, "42").await.unwrap();
+            generator.generate_icon(&[(41, ChargeState::Discharging)], "41").await.unwrap();
+            assert!(generator.generate_icon(&[(101, ChargeState::Full)], "101").await.is_err());
+        });
+        assert_eq!(cached(&generator), [41, 42]);
 
-        self.render_icon(x, y, scale, &text)
-            .context("Failed to render icon")
+        // 样式变化后旧图标作废
+        generator
+            .set_style(IconStyle { mode: IconMode::Glyph, ..IconStyle::default() })
+            .unwrap();
+        assert!(cached(&generator).is_empty());
     }
 }
@@ -0,0 +1,366 @@
+mod scripted;
+mod sysfs;
+mod system;
+#[cfg(target_os = "linux")]
+mod upower;
+
+pub use scripted::ScriptedSource;
+pub use sysfs::SysfsSource;
+pub use system::SystemSource;
+#[cfg(target_os = "linux")]
+pub use upower::UpowerSource;
+
+use std::{env, fmt, path::PathBuf, str::FromStr, time::Duration};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::{
+    change_watcher::{Backoff, ChangeWatcher, MonitorConfig},
+    health::BatteryHealth,
+};
+
+/// 单次读取到的电池数据
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatteryReading {
+    /// 电池名称，如 BAT0。外设为序列号等稳定且唯一的标识，用于固定和按设备设置样式
+    pub name: String,
+    /// 外设型号，如 MX Master 3，作为显示名称
+    pub model: Option<String>,
+    /// 电量百分比（0-100）
+    pub percentage: u32,
+    pub state: ChargeState,
+    /// 当前能量（Wh）
+    pub energy: Option<f32>,
+    /// 充满时的能量（Wh）
+    pub energy_full: Option<f32>,
+    /// 充放电功率（W）
+    pub energy_rate: Option<f32>,
+    /// 电压（V）
+    pub voltage: Option<f32>,
+    /// 温度（℃）
+    pub temperature: Option<f32>,
+    /// 系统报告的剩余放电时间
+    pub time_to_empty: Option<Duration>,
+    /// 系统报告的剩余充电时间
+    pub time_to_full: Option<Duration>,
+}
+
+impl BatteryReading {
+    /// 只有电量和状态的读数，其余指标留空
+    pub fn new(name: impl Into<String>, percentage: u32, state: ChargeState) -> Self {
+        Self {
+            name: name.into(),
+            model: None,
+            percentage,
+            state,
+            energy: None,
+            energy_full: None,
+            energy_rate: None,
+            voltage: None,
+            temperature: None,
+            time_to_empty: None,
+            time_to_full: None,
+        }
+    }
+
+    /// 显示名称：有型号时用型号，否则用名称
+    pub fn label(&self) -> &str {
+        self.model.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// 电池数据源
+pub trait BatterySource {
+    /// 读取一次所有电池的数据，没有电池时返回空列表
+    fn poll(&mut self) -> Result<Vec<BatteryReading>>;
+
+    /// 读取各电池的健康信息（容量、循环次数等），默认不提供
+    fn health(&mut self) -> Result<Vec<BatteryHealth>> {
+        Ok(Vec::new())
+    }
+
+    /// 决定两次读取之间如何等待，默认按固定间隔轮询
+    fn watcher(&self, config: &MonitorConfig) -> ChangeWatcher {
+        ChangeWatcher::polling(config.poll_interval)
+    }
+
+    /// 持续读取，数据变化或读取出错时发布到 tx，直到所有接收端关闭或 stop 返回 true。
+    /// 读取出错时按 Backoff 逐渐拉长重试间隔
+    fn subscribe(
+        &mut self,
+        config: &MonitorConfig,
+        tx: &watch::Sender<Option<SourceUpdate>>,
+        stop: &dyn Fn() -> bool,
+    ) -> Result<()> {
+        let mut watcher = self.watcher(config);
+        let mut backoff = Backoff::new(config.poll_interval);
+
+        while !stop() {
+            if tx.is_closed() {
+                return Ok(());
+            }
+            let update = match self.poll() {
+                Ok(readings) => SourceUpdate::Readings(readings),
+                Err(e) => {
+                    eprintln!("Failed to read battery: {:#}", e);
+                    SourceUpdate::Error(format!("{:#}", e))
+                }
+            };
+            let failed = matches!(update, SourceUpdate::Error(_));
+            update.publish(tx);
+
+            if failed {
+                backoff.wait(stop);
+            } else {
+                backoff.reset();
+                watcher.wait(stop);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 电池监视器发布的消息，托盘与 HTTP 服务都订阅它
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceUpdate {
+    /// 最新的读数，没有电池（如台式机）时为空
+    Readings(Vec<BatteryReading>),
+    /// 数据源无法使用时的错误信息，监视器会在稍后重试
+    Error(String),
+}
+
+impl SourceUpdate {
+    /// 发布到 tx，只有与当前值不同时才通知订阅者
+    pub fn publish(self, tx: &watch::Sender<Option<SourceUpdate>>) {
+        tx.send_if_modified(|current| {
+            if current.as_ref() == Some(&self) {
+                return false;
+            }
+            *current = Some(self);
+            true
+        });
+    }
+}
+
+/// 数据源类型，可在设置中选择，环境变量 PERCENTAGE_SOURCE 优先
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum SourceKind {
+    /// 系统电池（battery crate）
+    #[default]
+    System,
+    /// 脚本数据，未指定文件时使用内置的演示序列
+    Scripted(Option<PathBuf>),
+    /// Linux sysfs 目录，如 /sys/class/power_supply
+    Sysfs(PathBuf),
+    /// Linux 上的 UPower 服务，display_device 为 true 时只读取 UPower 汇总的 DisplayDevice
+    Upower { display_device: bool },
+}
+
+impl SourceKind {
+    const ENV_VAR: &'static str = "PERCENTAGE_SOURCE";
+
+    /// 从环境变量读取数据源类型，未设置时返回 None
+    pub fn from_env() -> Result<Option<Self>> {
+        match env::var(Self::ENV_VAR) {
+            Ok(value) => value
+                .parse()
+                .map(Some)
+                .with_context(|| format!("Invalid {} value", Self::ENV_VAR)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// 创建对应的数据源
+    pub fn open(&self) -> Result<Box<dyn BatterySource>> {
+        let source: Box<dyn BatterySource> = match self {
+            SourceKind::System => Box::new(SystemSource::new()?),
+            SourceKind::Scripted(None) => Box::new(ScriptedSource::demo()),
+            SourceKind::Scripted(Some(path)) => Box::new(ScriptedSource::from_file(path)?),
+            SourceKind::Sysfs(path) => Box::new(SysfsSource::new(path)),
+            #[cfg(target_os = "linux")]
+            SourceKind::Upower { display_device } => Box::new(UpowerSource::new(*display_device)?),
+            #[cfg(not(target_os = "linux"))]
+            SourceKind::Upower { .. } => bail!("The upower battery source is only available on Linux"),
+        };
+        Ok(source)
+    }
+}
+
+impl FromStr for SourceKind {
+    type Err = anyhow::Error;
+
+    /// 格式为 `system`、`scripted[:文件]`、`sysfs[:目录]` 或 `upower[:display]`
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, arg) = match s.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg)),
+            None => (s, None),
+        };
+        match (kind.trim(), arg) {
+            ("system", None) => Ok(SourceKind::System),
+            ("scripted", arg) => Ok(SourceKind::Scripted(arg.map(PathBuf::from))),
+            ("sysfs", arg) => Ok(SourceKind::Sysfs(
+                arg.map(PathBuf::from)
+                    .unwrap_or_else(|| PathBuf::from(SysfsSource::DEFAULT_ROOT)),
+            )),
+            ("upower", None) => Ok(SourceKind::Upower { display_device: false }),
+            ("upower", Some("display")) => Ok(SourceKind::Upower { display_device: true }),
+            _ => bail!("Unknown battery source: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for SourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceKind::System => write!(f, "system"),
+            SourceKind::Scripted(None) => write!(f, "scripted"),
+            SourceKind::Scripted(Some(path)) => write!(f, "scripted:{}", path.display()),
+            SourceKind::Sysfs(path) => write!(f, "sysfs:{}", path.display()),
+            SourceKind::Upower { display_device: false } => write!(f, "upower"),
+            SourceKind::Upower { display_device: true } => write!(f, "upower:display"),
+        }
+    }
+}
+
+impl TryFrom<String> for SourceKind {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<SourceKind> for String {
+    fn from(kind: SourceKind) -> Self {
+        kind.to_string()
+    }
+}
+
+/// 电池的充放电状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ChargeState {
+    Charging,
+    Discharging,
+    Full,
+    Empty,
+    /// 接通电源但没有充电，常见于设置了充电上限（如 80%）的笔记本
+    NotCharging,
+    Unknown,
+}
+
+impl ChargeState {
+    pub const ALL: [ChargeState; 6] = [
+        ChargeState::Charging,
+        ChargeState::Discharging,
+        ChargeState::Full,
+        ChargeState::Empty,
+        ChargeState::NotCharging,
+        ChargeState::Unknown,
+    ];
+
+    /// 低于此功率（W）视为没有充放电
+    const IDLE_RATE: f32 = 0.05;
+
+    /// 转换 battery crate 的状态。battery crate 把“接通电源但未充电”报告为 Unknown，
+    /// 此时功率接近 0，可据此区分
+    pub fn from_battery(state: battery::State, energy_rate: Option<f32>) -> Self {
+        match state {
+            battery::State::Charging => ChargeState::Charging,
+            battery::State::Discharging => ChargeState::Discharging,
+            battery::State::Full => ChargeState::Full,
+            battery::State::Empty => ChargeState::Empty,
+            _ if energy_rate.is_some_and(|rate| rate.abs() < Self::IDLE_RATE) => ChargeState::NotCharging,
+            _ => ChargeState::Unknown,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChargeState::Charging => "charging",
+            ChargeState::Discharging => "discharging",
+            ChargeState::Full => "full",
+            ChargeState::Empty => "empty",
+            ChargeState::NotCharging => "not-charging",
+            ChargeState::Unknown => "unknown",
+        }
+    }
+
+    /// 显示给用户的名称
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChargeState::Charging => "Charging",
+            ChargeState::Discharging => "Discharging",
+            ChargeState::Full => "Full",
+            ChargeState::Empty => "Empty",
+            ChargeState::NotCharging => "Plugged in, not charging",
+            ChargeState::Unknown => "Unknown",
+        }
+    }
+
+    /// 是否接通了电源
+    pub fn is_plugged_in(&self) -> bool {
+        matches!(self, ChargeState::Charging | ChargeState::Full | ChargeState::NotCharging)
+    }
+}
+
+impl FromStr for ChargeState {
+    type Err = anyhow::Error;
+
+    /// 兼容 sysfs 的 status 文件（如 `Not charging`）和脚本文件（如 `not-charging`）
+    fn from_str(s: &str) -> Result<Self> {
+        let normalized = s.trim().to_ascii_lowercase().replace([' ', '_'], "-");
+        match ChargeState::ALL.into_iter().find(|state| state.as_str() == normalized) {
+            Some(state) => Ok(state),
+            None => bail!("Unknown state: {}", s.trim()),
+        }
+    }
+}
+
+impl TryFrom<String> for ChargeState {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<ChargeState> for String {
+    fn from(state: ChargeState) -> Self {
+        state.as_str().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_kind_round_trips() {
+        for s in ["system", "scripted", "scripted:/tmp/demo.txt", "sysfs:/tmp/power_supply", "upower", "upower:display"] {
+            let kind: SourceKind = s.parse().unwrap();
+            assert_eq!(kind.to_string(), s);
+        }
+        assert_eq!(
+            "sysfs".parse::<SourceKind>().unwrap(),
+            SourceKind::Sysfs(PathBuf::from(SysfsSource::DEFAULT_ROOT))
+        );
+    }
+
+    #[test]
+    fn source_kind_rejects_unknown_values() {
+        for s in ["", "battery", "system:extra", "upower:all"] {
+            assert!(s.parse::<SourceKind>().is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn charge_state_accepts_sysfs_and_script_spellings() {
+        assert_eq!("Not charging".parse::<ChargeState>().unwrap(), ChargeState::NotCharging);
+        assert_eq!("not-charging".parse::<ChargeState>().unwrap(), ChargeState::NotCharging);
+        assert_eq!(" Full\n".parse::<ChargeState>().unwrap(), ChargeState::Full);
+        assert!("sideways".parse::<ChargeState>().is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use std::{fs, path::Path};
+use anyhow::{ensure, Context, Result};
+
+use super::{BatteryReading, BatterySource, ChargeState};
+
+/// 按预设序列循环返回读数的假数据源，用于没有电池的机器上调试托盘
+pub struct ScriptedSource {
+    steps: Vec<Vec<BatteryReading>>,
+    index: usize,
+}
+
+impl ScriptedSource {
+    pub fn new(steps: Vec<Vec<BatteryReading>>) -> Self {
+        Self { steps, index: 0 }
+    }
+
+    /// 演示序列：从 100% 放电到 0%，再充电到充满
+    pub fn demo() -> Self {
+        let step = |p, state| vec![BatteryReading::new("Scripted", p, state)];
+        let discharge = (0..=100).rev().map(|p| step(p, ChargeState::Discharging));
+        let charge = (0..100).map(|p| step(p, ChargeState::Charging));
+        let full = std::iter::once(step(100, ChargeState::Full));
+        Self::new(discharge.chain(charge).chain(full).collect())
+    }
+
+    /// 从文件读取序列，每行格式为 `百分比 状态`，如 `42 charging`、`80 not-charging`，# 开头为注释。
+    /// 多块电池用分号分隔，如 `80 discharging; 45 charging`
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read script {}", path.display()))?;
+
+        let mut steps = Vec::new();
+        for (line_no, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let step = parse_step(line)
+                .with_context(|| format!("{}:{}: invalid step", path.display(), line_no + 1))?;
+            steps.push(step);
+        }
+        ensure!(!steps.is_empty(), "Script contains no steps");
+
+        Ok(Self::new(steps))
+    }
+}
+
+fn parse_step(line: &str) -> Result<Vec<BatteryReading>> {
+    line.split(';')
+        .enumerate()
+        .map(|(index, battery)| parse_battery(index, battery.trim()))
+        .collect()
+}
+
+fn parse_battery(index: usize, s: &str) -> Result<BatteryReading> {
+    let (percentage, state) = s.split_once(char::is_whitespace).unwrap_or((s, "discharging"));
+    let percentage: u32 = percentage.parse().context("Invalid percentage")?;
+    ensure!(percentage <= 100, "Percentage must be between 0 and 100");
+    let state: ChargeState = state.parse()?;
+    Ok(BatteryReading::new(format!("BAT{}", index), percentage, state))
+}
+
+impl BatterySource for ScriptedSource {
+    fn poll(&mut self) -> Result<Vec<BatteryReading>> {
+        if self.steps.is_empty() {
+            return Ok(Vec::new());
+        }
+        let readings = self.steps[self.index % self.steps.len()].clone();
+        self.index += 1;
+        Ok(readings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn script(name: &str, content: &str) -> std::path::PathBuf {
+        let path = env::temp_dir().join(format!("percentage-rust-{}-{}.txt", name, std::process::id()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn parses_steps_and_loops() {
+        let path = script("script-steps", "# demo\n\n42 charging\n80 not-charging; 45\n");
+        let mut source = ScriptedSource::from_file(&path).unwrap();
+
+        assert_eq!(source.poll().unwrap(), [BatteryReading::new("BAT0", 42, ChargeState::Charging)]);
+        assert_eq!(
+            source.poll().unwrap(),
+            [
+                BatteryReading::new("BAT0", 80, ChargeState::NotCharging),
+                // 省略状态时视为放电
+                BatteryReading::new("BAT1", 45, ChargeState::Discharging),
+            ]
+        );
+        assert_eq!(source.poll().unwrap()[0].percentage, 42);
+    }
+
+    #[test]
+    fn reports_the_invalid_line() {
+        let path = script("script-invalid", "42 charging\n101 full\n");
+        let error = format!("{:#}", ScriptedSource::from_file(&path).err().unwrap());
+        assert!(error.contains(":2: invalid step"), "{}", error);
+        assert!(error.contains("between 0 and 100"), "{}", error);
+
+        let path = script("script-state", "50 sideways\n");
+        assert!(ScriptedSource::from_file(&path).is_err());
+    }
+
+    #[test]
+    fn rejects_empty_scripts() {
+        let path = script("script-empty", "# nothing\n\n");
+        assert!(ScriptedSource::from_file(&path).is_err());
+    }
+}
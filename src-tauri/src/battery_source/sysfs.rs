@@ -0,0 +1,394 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use anyhow::{Context, Result};
+
+use crate::{
+    change_watcher::{ChangeWatcher, MonitorConfig},
+    health::BatteryHealth,
+};
+
+use super::{BatteryReading, BatterySource, ChargeState};
+
+/// 直接读取 Linux power_supply 目录的数据源，目录可以指向任意位置以便测试
+pub struct SysfsSource {
+    root: PathBuf,
+    /// 读取鼠标、键盘等外设（scope 为 Device），而不是为电脑供电的电池
+    peripherals: bool,
+}
+
+impl SysfsSource {
+    pub const DEFAULT_ROOT: &'static str = "/sys/class/power_supply";
+
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            peripherals: false,
+        }
+    }
+
+    /// 只读取外设的电池，如蓝牙鼠标、键盘和耳机
+    pub fn peripherals(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            peripherals: true,
+        }
+    }
+
+    /// 找到所有 type 为 Battery 的设备目录，按名称排序。外设的 scope 为 Device，
+    /// 按 peripherals 只保留外设或只保留电脑的电池
+    fn find_batteries(&self) -> Result<Vec<PathBuf>> {
+        let entries = fs::read_dir(&self.root)
+            .with_context(|| format!("Failed to read {}", self.root.display()))?;
+
+        let mut devices: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+        devices.sort();
+
+        devices.retain(|dir| {
+            read_attr(dir, "type").as_deref() == Some("Battery")
+                && (read_attr(dir, "scope").as_deref() == Some("Device")) == self.peripherals
+        });
+        Ok(devices)
+    }
+}
+
+impl BatterySource for SysfsSource {
+    /// 电池变化会产生 power_supply 事件，Linux 上无需频繁轮询
+    fn watcher(&self, config: &MonitorConfig) -> ChangeWatcher {
+        ChangeWatcher::new(config)
+    }
+
+    fn poll(&mut self) -> Result<Vec<BatteryReading>> {
+        Ok(self
+            .find_batteries()?
+            .iter()
+            .filter_map(|dir| {
+                let mut reading = read_device(dir)?;
+                // 外设的目录名（如 hidpp_battery_0）每次连接都可能变化，有序列号时改用序列号作为名称，
+                // 同型号的多个设备也不会混淆；型号只用于显示
+                if self.peripherals {
+                    if let Some(serial) = read_attr(dir, "serial_number").filter(|s| !s.is_empty()) {
+                        reading.name = serial;
+                    }
+                    reading.model = read_attr(dir, "model_name").filter(|s| !s.is_empty());
+                }
+                Some(reading)
+            })
+            .collect())
+    }
+
+    fn health(&mut self) -> Result<Vec<BatteryHealth>> {
+        Ok(self.find_batteries()?.iter().map(|dir| read_health(dir)).collect())
+    }
+}
+
+/// 读取单个属性文件，去掉首尾空白
+fn read_attr(dir: &Path, name: &str) -> Option<String> {
+    fs::read_to_string(dir.join(name))
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+/// 读取数值属性并按比例换算（sysfs 以微单位记录）
+fn read_scaled(dir: &Path, name: &str, scale: f32) -> Option<f32> {
+    read_attr(dir, name)?.parse::<f32>().ok().map(|v| v * scale)
+}
+
+/// 读取以秒为单位的时间属性，0 表示驱动无法估算
+fn read_seconds(dir: &Path, name: &str) -> Option<Duration> {
+    read_attr(dir, name)?
+        .parse::<u64>()
+        .ok()
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs)
+}
+
+/// 读取电池设备目录中的健康信息，charge_* 按设计最低电压换算成能量
+fn read_health(dir: &Path) -> BatteryHealth {
+    let design_voltage = read_scaled(dir, "voltage_min_design", 1e-6);
+    let energy = |energy_attr: &str, charge_attr: &str| {
+        read_scaled(dir, energy_attr, 1e-6)
+            .or_else(|| Some(read_scaled(dir, charge_attr, 1e-6)? * design_voltage?))
+    };
+    BatteryHealth {
+        vendor: read_attr(dir, "manufacturer").filter(|s| !s.is_empty()),
+        model: read_attr(dir, "model_name").filter(|s| !s.is_empty()),
+        serial_number: read_attr(dir, "serial_number").filter(|s| !s.is_empty()),
+        technology: read_attr(dir, "technology").filter(|s| !s.is_empty()),
+        energy_full_design: energy("energy_full_design", "charge_full_design"),
+        energy_full: energy("energy_full", "charge_full"),
+        cycle_count: read_attr(dir, "cycle_count")
+            .and_then(|s| s.parse().ok())
+            .filter(|&count: &u32| count > 0),
+        ..BatteryHealth::new(device_name(dir))
+    }
+}
+
+/// 设备目录名，如 BAT0
+fn device_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// 部分外设只报告电量等级，换算成大致的百分比
+fn level_percentage(level: &str) -> Option<u32> {
+    match level {
+        "Full" => Some(100),
+        "High" => Some(80),
+        "Normal" => Some(50),
+        "Low" => Some(20),
+        "Critical" => Some(5),
+        _ => None,
+    }
+}
+
+/// 读取一个电池设备目录
+fn read_device(dir: &Path) -> Option<BatteryReading> {
+    let voltage = read_scaled(dir, "voltage_now", 1e-6);
+
+    // 部分设备只提供 charge_*（µAh）和 current_now（µA），借助电压换算成能量与功率
+    let energy = read_scaled(dir, "energy_now", 1e-6)
+        .or_else(|| Some(read_scaled(dir, "charge_now", 1e-6)? * voltage?));
+    let energy_full = read_scaled(dir, "energy_full", 1e-6)
+        .or_else(|| Some(read_scaled(dir, "charge_full", 1e-6)? * voltage?));
+    let energy_rate = read_scaled(dir, "power_now", 1e-6)
+        .or_else(|| Some(read_scaled(dir, "current_now", 1e-6)? * voltage?))
+        .map(f32::abs);
+
+    let percentage = read_attr(dir, "capacity")
+        .and_then(|s| s.parse::<u32>().ok())
+        .or_else(|| match (energy, energy_full) {
+            (Some(now), Some(full)) if full > 0.0 => Some((now / full * 100.0).round() as u32),
+            _ => None,
+        })
+        .or_else(|| read_attr(dir, "capacity_level").and_then(|level| level_percentage(&level)))?
+        .min(100);
+
+    let state = read_attr(dir, "status")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(ChargeState::Unknown);
+
+    let name = device_name(dir);
+
+    Some(BatteryReading {
+        name,
+        model: None,
+        percentage,
+        state,
+        energy,
+        energy_full,
+        energy_rate,
+        voltage,
+        temperature: read_scaled(dir, "temp", 0.1),
+        time_to_empty: read_seconds(dir, "time_to_empty_now"),
+        time_to_full: read_seconds(dir, "time_to_full_now"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    /// 在临时目录中搭建 power_supply 目录，每个设备是一组属性文件
+    fn fixture(name: &str, devices: &[(&str, &[(&str, &str)])]) -> PathBuf {
+        let root = env::temp_dir().join(format!("percentage-rust-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for (device, attrs) in devices {
+            let dir = root.join(device);
+            fs::create_dir_all(&dir).unwrap();
+            for (attr, value) in *attrs {
+                fs::write(dir.join(attr), format!("{}\n", value)).unwrap();
+            }
+        }
+        root
+    }
+
+    #[test]
+    fn reads_capacity_and_energy() {
+        let root = fixture(
+            "sysfs-energy",
+            &[(
+                "BAT0",
+                &[
+                    ("type", "Battery"),
+                    ("status", "Discharging"),
+                    ("capacity", "42"),
+                    ("energy_now", "21000000"),
+                    ("energy_full", "50000000"),
+                    ("power_now", "8500000"),
+                    ("time_to_empty_now", "0"),
+                ],
+            )],
+        );
+        let readings = SysfsSource::new(&root).poll().unwrap();
+        assert_eq!(readings.len(), 1);
+        let reading = &readings[0];
+        assert_eq!(reading.name, "BAT0");
+        assert_eq!(reading.percentage, 42);
+        assert_eq!(reading.state, ChargeState::Discharging);
+        assert_eq!(reading.energy, Some(21.0));
+        assert_eq!(reading.energy_rate, Some(8.5));
+        // 0 表示驱动无法估算
+        assert_eq!(reading.time_to_empty, None);
+    }
+
+    #[test]
+    fn falls_back_to_energy_ratio_without_capacity() {
+        let root = fixture(
+            "sysfs-no-capacity",
+            &[(
+                "BAT0",
+                &[("type", "Battery"), ("energy_now", "15000000"), ("energy_full", "60000000")],
+            )],
+        );
+        let readings = SysfsSource::new(&root).poll().unwrap();
+        assert_eq!(readings[0].percentage, 25);
+        assert_eq!(readings[0].state, ChargeState::Unknown);
+    }
+
+    #[test]
+    fn converts_charge_and_current_with_voltage() {
+        let root = fixture(
+            "sysfs-charge",
+            &[(
+                "BAT1",
+                &[
+                    ("type", "Battery"),
+                    ("status", "Charging"),
+                    ("voltage_now", "12000000"),
+                    ("charge_now", "2500000"),
+                    ("charge_full", "5000000"),
+                    ("current_now", "-1000000"),
+                ],
+            )],
+        );
+        let reading = &SysfsSource::new(&root).poll().unwrap()[0];
+        assert_eq!(reading.voltage, Some(12.0));
+        assert_eq!(reading.energy, Some(30.0));
+        assert_eq!(reading.energy_full, Some(60.0));
+        assert_eq!(reading.energy_rate, Some(12.0));
+        assert_eq!(reading.percentage, 50);
+    }
+
+    #[test]
+    fn separates_peripherals_by_scope() {
+        let root = fixture(
+            "sysfs-scope",
+            &[
+                ("AC", &[("type", "Mains"), ("online", "1")]),
+                ("BAT0", &[("type", "Battery"), ("capacity", "80")]),
+                (
+                    "hidpp_battery_0",
+                    &[("type", "Battery"), ("scope", "Device"), ("capacity_level", "Low")],
+                ),
+            ],
+        );
+        let batteries = SysfsSource::new(&root).poll().unwrap();
+        assert_eq!(batteries.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(), ["BAT0"]);
+
+        let peripherals = SysfsSource::peripherals(&root).poll().unwrap();
+        assert_eq!(peripherals.len(), 1);
+        assert_eq!(peripherals[0].name, "hidpp_battery_0");
+        assert_eq!(peripherals[0].percentage, 20);
+    }
+
+    #[test]
+    fn names_peripherals_by_serial_and_labels_them_by_model() {
+        const MOUSE: [(&str, &str); 4] = [
+            ("type", "Battery"),
+            ("scope", "Device"),
+            ("capacity", "55"),
+            ("model_name", "MX Master 3"),
+        ];
+        let with_serial = |serial| {
+            let mut attrs = MOUSE.to_vec();
+            attrs.push(("serial_number", serial));
+            attrs
+        };
+        let root = fixture(
+            "sysfs-serial",
+            &[
+                ("hidpp_battery_0", &with_serial("4032-aa")),
+                ("hidpp_battery_1", &with_serial("4032-bb")),
+                // 没有序列号时退回目录名
+                ("hidpp_battery_2", &MOUSE),
+            ],
+        );
+
+        let peripherals = SysfsSource::peripherals(&root).poll().unwrap();
+        let names: Vec<&str> = peripherals.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["4032-aa", "4032-bb", "hidpp_battery_2"]);
+        assert!(peripherals.iter().all(|r| r.label() == "MX Master 3"));
+    }
+
+    #[test]
+    fn reads_health_in_watt_hours() {
+        let root = fixture(
+            "sysfs-health-energy",
+            &[(
+                "BAT0",
+                &[
+                    ("type", "Battery"),
+                    ("manufacturer", "SMP"),
+                    ("model_name", "5B10W13930"),
+                    ("serial_number", ""),
+                    ("energy_full_design", "57000000"),
+                    ("energy_full", "45600000"),
+                    ("cycle_count", "0"),
+                ],
+            )],
+        );
+        let health = &SysfsSource::new(&root).health().unwrap()[0];
+        assert_eq!(health.name, "BAT0");
+        assert_eq!(health.vendor.as_deref(), Some("SMP"));
+        assert_eq!(health.model.as_deref(), Some("5B10W13930"));
+        assert_eq!(health.serial_number, None);
+        assert_eq!(health.energy_full_design, Some(57.0));
+        assert_eq!(health.energy_full, Some(45.6));
+        // 0 表示驱动不统计循环次数
+        assert_eq!(health.cycle_count, None);
+        assert!((health.wear().unwrap() - 20.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn converts_health_charge_with_design_voltage() {
+        let root = fixture(
+            "sysfs-health-charge",
+            &[
+                (
+                    "BAT0",
+                    &[
+                        ("type", "Battery"),
+                        ("voltage_min_design", "11400000"),
+                        // 当前电压会随电量变化，容量只按设计电压换算
+                        ("voltage_now", "12600000"),
+                        ("charge_full_design", "5000000"),
+                        ("charge_full", "4000000"),
+                        ("cycle_count", "312"),
+                    ],
+                ),
+                ("BAT1", &[("type", "Battery"), ("charge_full_design", "5000000"), ("charge_full", "4000000")]),
+            ],
+        );
+        let health = SysfsSource::new(&root).health().unwrap();
+        let approx = |value: Option<f32>, expected: f32| (value.unwrap() - expected).abs() < 1e-3;
+        assert!(approx(health[0].energy_full_design, 57.0), "{:?}", health[0]);
+        assert!(approx(health[0].energy_full, 45.6), "{:?}", health[0]);
+        assert!(approx(health[0].wear(), 20.0));
+        assert_eq!(health[0].cycle_count, Some(312));
+        // 没有设计电压时无法换算成能量
+        assert_eq!(health[1].energy_full_design, None);
+        assert_eq!(health[1].wear(), None);
+    }
+
+    #[test]
+    fn missing_root_is_an_error() {
+        let root = env::temp_dir().join("percentage-rust-sysfs-missing");
+        assert!(SysfsSource::new(root).poll().is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use std::time::Duration;
+use anyhow::{Context, Result};
+use battery::{
+    units::{
+        electric_potential::volt, energy::watt_hour, power::watt,
+        thermodynamic_temperature::degree_celsius, time::second,
+    },
+    Battery, Manager,
+};
+
+use crate::{
+    change_watcher::{ChangeWatcher, MonitorConfig},
+    health::BatteryHealth,
+};
+
+use super::{BatteryReading, BatterySource, ChargeState};
+
+/// 通过 battery crate 读取系统电池
+pub struct SystemSource {
+    manager: Manager,
+}
+
+impl SystemSource {
+    pub fn new() -> Result<Self> {
+        let manager = Manager::new().context("Failed to initialize battery manager")?;
+        Ok(Self { manager })
+    }
+}
+
+impl BatterySource for SystemSource {
+    /// 电池变化会产生 power_supply 事件，Linux 上无需频繁轮询
+    fn watcher(&self, config: &MonitorConfig) -> ChangeWatcher {
+        ChangeWatcher::new(config)
+    }
+
+    fn poll(&mut self) -> Result<Vec<BatteryReading>> {
+        let batteries = self.manager.batteries().context("Failed to enumerate batteries")?;
+        Ok(batteries
+            .flatten()
+            .enumerate()
+            .map(|(index, battery)| reading_from_battery(index, &battery))
+            .collect())
+    }
+
+    fn health(&mut self) -> Result<Vec<BatteryHealth>> {
+        let batteries = self.manager.batteries().context("Failed to enumerate batteries")?;
+        Ok(batteries
+            .flatten()
+            .enumerate()
+            .map(|(index, battery)| BatteryHealth {
+                vendor: battery.vendor().map(str::to_string),
+                model: battery.model().map(str::to_string),
+                serial_number: battery.serial_number().map(str::to_string),
+                technology: Some(battery.technology().to_string()),
+                energy_full_design: Some(battery.energy_full_design().get::<watt_hour>()),
+                energy_full: Some(battery.energy_full().get::<watt_hour>()),
+                cycle_count: battery.cycle_count(),
+                ..BatteryHealth::new(battery_name(index))
+            })
+            .collect())
+    }
+}
+
+/// battery crate 不提供设备名，用序号代替
+fn battery_name(index: usize) -> String {
+    format!("Battery {}", index + 1)
+}
+
+/// 将 battery crate 的电池对象转换为读数
+fn reading_from_battery(index: usize, battery: &Battery) -> BatteryReading {
+    let energy_rate = battery.energy_rate().get::<watt>();
+    BatteryReading {
+        name: battery_name(index),
+        model: None,
+        percentage: (battery.state_of_charge().value * 100.0).round() as u32,
+        state: ChargeState::from_battery(battery.state(), Some(energy_rate)),
+        energy: Some(battery.energy().get::<watt_hour>()),
+        energy_full: Some(battery.energy_full().get::<watt_hour>()),
+        energy_rate: Some(energy_rate),
+        voltage: Some(battery.voltage().get::<volt>()),
+        temperature: battery.temperature().map(|t| t.get::<degree_celsius>()),
+        time_to_empty: battery.time_to_empty().map(|t| Duration::from_secs_f32(t.get::<second>())),
+        time_to_full: battery.time_to_full().map(|t| Duration::from_secs_f32(t.get::<second>())),
+    }
+}
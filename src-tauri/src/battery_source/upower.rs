@@ -0,0 +1,590 @@
+use std::{
+    collections::HashMap,
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use anyhow::{Context, Result};
+use zbus::{
+    blocking::{Connection, MessageIterator},
+    message::Type,
+    zvariant::{OwnedObjectPath, OwnedValue},
+    MatchRule,
+};
+
+use crate::{
+    change_watcher::{ChangeWatcher, MonitorConfig},
+    health::BatteryHealth,
+};
+
+use super::{BatteryReading, BatterySource, ChargeState};
+
+const SERVICE: &str = "org.freedesktop.UPower";
+const PATH: &str = "/org/freedesktop/UPower";
+const INTERFACE: &str = "org.freedesktop.UPower";
+const DEVICE_INTERFACE: &str = "org.freedesktop.UPower.Device";
+
+/// UPower 设备类型中的电源适配器与电池
+const TYPE_LINE_POWER: u32 = 1;
+const TYPE_BATTERY: u32 = 2;
+
+/// 读取 UPower 的哪些设备
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    /// 为电脑供电的各块电池
+    Batteries,
+    /// UPower 汇总后的 DisplayDevice
+    DisplayDevice,
+    /// 不为电脑供电的外设，如蓝牙鼠标、键盘和耳机
+    Peripherals,
+}
+
+/// 通过系统总线上的 UPower 读取电池。UPower 的属性变化会以信号推送，无需频繁轮询。
+/// 连接遵循 DBUS_SYSTEM_BUS_ADDRESS，可以指向模拟的 UPower 服务进行测试
+pub struct UpowerSource {
+    connection: Connection,
+    scope: Scope,
+    /// 信号线程把通知转发到这里，也就是最近创建的 watcher 的通道
+    subscriber: Arc<Mutex<Option<mpsc::Sender<()>>>>,
+    /// 转发 UPower 信号的线程，第一次创建 watcher 时启动，每个数据源只有一个
+    signal_thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl UpowerSource {
+    /// display_device 为 true 时只读取 UPower 汇总后的 DisplayDevice，而不是各块电池
+    pub fn new(display_device: bool) -> Result<Self> {
+        Self::open(if display_device { Scope::DisplayDevice } else { Scope::Batteries })
+    }
+
+    /// 只读取外设的电池
+    pub fn peripherals() -> Result<Self> {
+        Self::open(Scope::Peripherals)
+    }
+
+    fn open(scope: Scope) -> Result<Self> {
+        let connection = Connection::system().context("Failed to connect to the system bus")?;
+        Ok(Self::with_connection(connection, scope))
+    }
+
+    fn with_connection(connection: Connection, scope: Scope) -> Self {
+        Self {
+            connection,
+            scope,
+            subscriber: Arc::default(),
+            signal_thread: Mutex::new(None),
+        }
+    }
+
+    fn call_upower(&self, method: &str) -> Result<zbus::Message> {
+        self.connection
+            .call_method(Some(SERVICE), PATH, Some(INTERFACE), method, &())
+            .with_context(|| format!("Failed to call UPower {}", method))
+    }
+
+    /// 在线的电池设备及其属性，按路径排序。外设的 PowerSupply 为 false，
+    /// 按 peripherals 只保留外设或只保留电脑的电池
+    fn devices(&self, peripherals: bool) -> Result<Vec<DeviceProperties>> {
+        let mut paths: Vec<OwnedObjectPath> = self
+            .call_upower("EnumerateDevices")?
+            .body()
+            .deserialize()
+            .context("Invalid reply from UPower EnumerateDevices")?;
+        paths.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+        let mut devices = Vec::new();
+        for path in paths {
+            let properties = self.device_properties(&path)?;
+            if properties.is_wanted(peripherals) {
+                devices.push(properties);
+            }
+        }
+        Ok(devices)
+    }
+
+    fn device_properties(&self, path: &OwnedObjectPath) -> Result<DeviceProperties> {
+        let properties = self
+            .connection
+            .call_method(
+                Some(SERVICE),
+                path.clone(),
+                Some("org.freedesktop.DBus.Properties"),
+                "GetAll",
+                &(DEVICE_INTERFACE,),
+            )
+            .with_context(|| format!("Failed to read UPower device {}", path.as_str()))?
+            .body()
+            .deserialize()
+            .with_context(|| format!("Invalid properties of UPower device {}", path.as_str()))?;
+        Ok(DeviceProperties(properties))
+    }
+}
+
+impl UpowerSource {
+    /// 订阅 UPower 的信号并启动转发线程，线程仍在运行时不重复订阅
+    fn start_signal_thread(&self) -> Result<()> {
+        let mut signal_thread = self.signal_thread.lock().unwrap();
+        if signal_thread.as_ref().is_some_and(|thread| !thread.is_finished()) {
+            return Ok(());
+        }
+
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .path_namespace(PATH)
+            .map(|builder| builder.build());
+        let messages = rule
+            .and_then(|rule| MessageIterator::for_match_rule(rule, &self.connection, None))
+            .context("Failed to subscribe to UPower signals")?;
+        let subscriber = Arc::downgrade(&self.subscriber);
+        *signal_thread = Some(thread::spawn(move || {
+            for message in messages {
+                // 数据源已被丢弃时，在下一个信号到来时结束
+                let Some(subscriber) = subscriber.upgrade() else {
+                    return;
+                };
+                if message.is_err() {
+                    break;
+                }
+                let subscriber = subscriber.lock().unwrap();
+                if let Some(tx) = &*subscriber {
+                    let _ = tx.send(());
+                }
+            }
+            // 连接出错或断开：断开当前 watcher 的通道，让它退回轮询
+            if let Some(subscriber) = subscriber.upgrade() {
+                *subscriber.lock().unwrap() = None;
+            }
+        }));
+        Ok(())
+    }
+}
+
+impl BatterySource for UpowerSource {
+    /// 等待 UPower 的信号（PropertiesChanged、Changed、DeviceAdded 等），订阅失败时退回轮询
+    fn watcher(&self, config: &MonitorConfig) -> ChangeWatcher {
+        let (tx, rx) = mpsc::channel();
+        // 之前的 watcher 的通道随之断开，信号只转发给最新的 watcher
+        *self.subscriber.lock().unwrap() = Some(tx);
+        match self.start_signal_thread() {
+            Ok(()) => ChangeWatcher::from_events(rx, config),
+            Err(e) => {
+                eprintln!("{:#}, polling instead", e);
+                ChangeWatcher::polling(config.poll_interval)
+            }
+        }
+    }
+
+    fn poll(&mut self) -> Result<Vec<BatteryReading>> {
+        match self.scope {
+            Scope::DisplayDevice => {
+                let path: OwnedObjectPath = self
+                    .call_upower("GetDisplayDevice")?
+                    .body()
+                    .deserialize()
+                    .context("Invalid reply from UPower GetDisplayDevice")?;
+                let properties = self.device_properties(&path)?;
+                // 没有电池时 DisplayDevice 仍然存在，但 IsPresent 为 false
+                if !properties.get::<bool>("IsPresent").unwrap_or(false) {
+                    return Ok(Vec::new());
+                }
+                Ok(vec![properties.reading("Battery".to_string())])
+            }
+            Scope::Batteries => Ok(self
+                .devices(false)?
+                .into_iter()
+                .enumerate()
+                .map(|(index, properties)| {
+                    let name = properties.name().unwrap_or_else(|| format!("Battery {}", index + 1));
+                    properties.reading(name)
+                })
+                .collect()),
+            // 外设以序列号区分同型号的设备，型号（如 MX Master 3）只用于显示
+            Scope::Peripherals => Ok(self
+                .devices(true)?
+                .into_iter()
+                .enumerate()
+                .map(|(index, properties)| {
+                    let name = properties
+                        .text("Serial")
+                        .or_else(|| properties.name())
+                        .unwrap_or_else(|| format!("Device {}", index + 1));
+                    BatteryReading {
+                        model: properties.text("Model"),
+                        ..properties.reading(name)
+                    }
+                })
+                .collect()),
+        }
+    }
+
+    fn health(&mut self) -> Result<Vec<BatteryHealth>> {
+        Ok(self
+            .devices(self.scope == Scope::Peripherals)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, properties)| {
+                let name = properties.name().unwrap_or_else(|| format!("Battery {}", index + 1));
+                properties.health(name)
+            })
+            .collect())
+    }
+}
+
+/// 一个 UPower 设备的全部属性
+struct DeviceProperties(HashMap<String, OwnedValue>);
+
+impl DeviceProperties {
+    fn get<'a, T: TryFrom<&'a OwnedValue>>(&'a self, name: &str) -> Option<T> {
+        self.0.get(name).and_then(|value| T::try_from(value).ok())
+    }
+
+    /// 文本属性，空字符串视为缺失
+    fn text(&self, name: &str) -> Option<String> {
+        self.get::<&str>(name).filter(|s| !s.is_empty()).map(str::to_string)
+    }
+
+    /// UPower 用 0 表示未知的数值
+    fn positive(&self, name: &str) -> Option<f32> {
+        self.get::<f64>(name).filter(|&v| v > 0.0).map(|v| v as f32)
+    }
+
+    fn seconds(&self, name: &str) -> Option<Duration> {
+        self.get::<i64>(name)
+            .filter(|&secs| secs > 0)
+            .map(|secs| Duration::from_secs(secs as u64))
+    }
+
+    /// 在线且属于所需类别：peripherals 为 true 时是不为电脑供电的外设（排除电源适配器），
+    /// 否则是为电脑供电的电池
+    fn is_wanted(&self, peripherals: bool) -> bool {
+        let kind = self.get::<u32>("Type");
+        let power_supply = self.get::<bool>("PowerSupply").unwrap_or(true);
+        let wanted = if peripherals {
+            !power_supply && kind != Some(TYPE_LINE_POWER)
+        } else {
+            power_supply && kind == Some(TYPE_BATTERY)
+        };
+        wanted && self.get::<bool>("IsPresent").unwrap_or(true)
+    }
+
+    /// 设备名取自 NativePath，如 BAT0
+    fn name(&self) -> Option<String> {
+        self.text("NativePath")
+            .map(|path| path.rsplit('/').next().unwrap_or(&path).to_string())
+    }
+
+    fn state(&self) -> ChargeState {
+        match self.get::<u32>("State") {
+            Some(1) => ChargeState::Charging,
+            Some(2) => ChargeState::Discharging,
+            Some(3) => ChargeState::Empty,
+            Some(4) => ChargeState::Full,
+            // 等待充电或等待放电：接通电源但没有充电
+            Some(5) | Some(6) => ChargeState::NotCharging,
+            _ => ChargeState::Unknown,
+        }
+    }
+
+    fn reading(&self, name: String) -> BatteryReading {
+        let percentage = self.get::<f64>("Percentage").unwrap_or(0.0).round().clamp(0.0, 100.0) as u32;
+        BatteryReading {
+            energy: self.get::<f64>("Energy").map(|v| v as f32),
+            energy_full: self.positive("EnergyFull"),
+            energy_rate: self.get::<f64>("EnergyRate").map(|v| v.abs() as f32),
+            voltage: self.positive("Voltage"),
+            temperature: self.positive("Temperature"),
+            time_to_empty: self.seconds("TimeToEmpty"),
+            time_to_full: self.seconds("TimeToFull"),
+            ..BatteryReading::new(name, percentage, self.state())
+        }
+    }
+
+    fn health(&self, name: String) -> BatteryHealth {
+        let technology = match self.get::<u32>("Technology") {
+            Some(1) => Some("Li-ion"),
+            Some(2) => Some("Li-poly"),
+            Some(3) => Some("LiFePO4"),
+            Some(4) => Some("Lead acid"),
+            Some(5) => Some("NiCd"),
+            Some(6) => Some("NiMH"),
+            _ => None,
+        };
+        BatteryHealth {
+            vendor: self.text("Vendor"),
+            model: self.text("Model"),
+            serial_number: self.text("Serial"),
+            technology: technology.map(str::to_string),
+            energy_full_design: self.positive("EnergyFullDesign"),
+            energy_full: self.positive("EnergyFull"),
+            cycle_count: self.get::<i32>("ChargeCycles").filter(|&c| c > 0).map(|c| c as u32),
+            ..BatteryHealth::new(name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::net::UnixStream, time::Instant};
+    use zbus::{blocking::connection, interface, zvariant::Value, Guid};
+
+    use super::*;
+
+    /// 模拟的 UPower 服务
+    struct MockUpower {
+        devices: Vec<OwnedObjectPath>,
+    }
+
+    #[interface(name = "org.freedesktop.UPower")]
+    impl MockUpower {
+        fn enumerate_devices(&self) -> Vec<OwnedObjectPath> {
+            self.devices.clone()
+        }
+
+        fn get_display_device(&self) -> OwnedObjectPath {
+            device_path("DisplayDevice")
+        }
+    }
+
+    /// 模拟的 UPower 设备，只提供读取时用到的属性
+    struct MockDevice {
+        native_path: &'static str,
+        kind: u32,
+        power_supply: bool,
+        percentage: f64,
+        state: u32,
+        model: &'static str,
+        serial: &'static str,
+    }
+
+    #[interface(name = "org.freedesktop.UPower.Device")]
+    impl MockDevice {
+        #[zbus(property)]
+        fn native_path(&self) -> String {
+            self.native_path.to_string()
+        }
+
+        #[zbus(property, name = "Type")]
+        fn kind(&self) -> u32 {
+            self.kind
+        }
+
+        #[zbus(property)]
+        fn power_supply(&self) -> bool {
+            self.power_supply
+        }
+
+        #[zbus(property)]
+        fn percentage(&self) -> f64 {
+            self.percentage
+        }
+
+        #[zbus(property)]
+        fn state(&self) -> u32 {
+            self.state
+        }
+
+        #[zbus(property)]
+        fn is_present(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn model(&self) -> String {
+            self.model.to_string()
+        }
+
+        #[zbus(property)]
+        fn serial(&self) -> String {
+            self.serial.to_string()
+        }
+    }
+
+    fn device_path(name: &str) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(format!("{}/devices/{}", PATH, name)).unwrap()
+    }
+
+    /// 通过点对点连接提供模拟的 UPower，返回服务端连接与连接到它的数据源
+    fn mock_upower(scope: Scope) -> (Connection, UpowerSource) {
+        let device = |native_path, kind, power_supply, percentage, state| MockDevice {
+            native_path,
+            kind,
+            power_supply,
+            percentage,
+            state,
+            model: "",
+            serial: "",
+        };
+        let mouse = MockDevice {
+            model: "MX Master 3",
+            serial: "4A-3B",
+            ..device("hidpp_battery_0", 5, false, 70.0, 2)
+        };
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            connection::Builder::unix_stream(server_stream)
+                .server(Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at(PATH, MockUpower {
+                    devices: ["battery_BAT1", "line_power_AC", "mouse_hidpp", "battery_BAT0"]
+                        .into_iter()
+                        .map(device_path)
+                        .collect(),
+                })
+                .unwrap()
+                .serve_at(device_path("battery_BAT0"), device("/sys/class/power_supply/BAT0", 2, true, 41.6, 2))
+                .unwrap()
+                .serve_at(device_path("battery_BAT1"), device("BAT1", 2, true, 90.0, 1))
+                .unwrap()
+                .serve_at(device_path("line_power_AC"), device("AC", 1, true, 0.0, 0))
+                .unwrap()
+                .serve_at(device_path("mouse_hidpp"), mouse)
+                .unwrap()
+                .serve_at(device_path("DisplayDevice"), device("", 2, true, 65.8, 2))
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = connection::Builder::unix_stream(client_stream).p2p().build().unwrap();
+        (server.join().unwrap(), UpowerSource::with_connection(client, scope))
+    }
+
+    fn emit_properties_changed(server: &Connection) {
+        server
+            .emit_signal(
+                None::<&str>,
+                device_path("battery_BAT0"),
+                "org.freedesktop.DBus.Properties",
+                "PropertiesChanged",
+                &(DEVICE_INTERFACE, HashMap::<&str, Value>::new(), Vec::<&str>::new()),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn polls_mock_upower() {
+        let (_server, mut source) = mock_upower(Scope::Batteries);
+        let readings = source.poll().unwrap();
+        let summary: Vec<(&str, u32, ChargeState)> =
+            readings.iter().map(|r| (r.name.as_str(), r.percentage, r.state)).collect();
+        assert_eq!(summary, [("BAT0", 42, ChargeState::Discharging), ("BAT1", 90, ChargeState::Charging)]);
+
+        let (_server, mut source) = mock_upower(Scope::DisplayDevice);
+        let readings = source.poll().unwrap();
+        assert_eq!(readings.len(), 1);
+        assert_eq!((readings[0].name.as_str(), readings[0].percentage), ("Battery", 66));
+
+        let (_server, mut source) = mock_upower(Scope::Peripherals);
+        let readings = source.poll().unwrap();
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].name, "4A-3B");
+        assert_eq!(readings[0].model.as_deref(), Some("MX Master 3"));
+    }
+
+    #[test]
+    fn watchers_share_one_signal_thread() {
+        let (server, source) = mock_upower(Scope::Batteries);
+        // 两个间隔都很长，wait 很快返回说明收到了信号
+        let config = MonitorConfig {
+            poll_interval: Duration::from_secs(30),
+            event_fallback_interval: Duration::from_secs(30),
+        };
+        let thread_id = || source.signal_thread.lock().unwrap().as_ref().map(|t| t.thread().id());
+
+        for _ in 0..3 {
+            let mut watcher = source.watcher(&config);
+            emit_properties_changed(&server);
+            let start = Instant::now();
+            watcher.wait(&|| false);
+            assert!(start.elapsed() < Duration::from_secs(5), "{:?}", start.elapsed());
+        }
+        let first = thread_id();
+        assert!(first.is_some());
+        source.watcher(&config);
+        assert_eq!(thread_id(), first);
+    }
+
+    fn properties<const N: usize>(values: [(&str, Value<'static>); N]) -> DeviceProperties {
+        DeviceProperties(
+            values
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.try_into().unwrap()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn maps_state_codes() {
+        let cases = [
+            (1, ChargeState::Charging),
+            (2, ChargeState::Discharging),
+            (3, ChargeState::Empty),
+            (4, ChargeState::Full),
+            (5, ChargeState::NotCharging),
+            (6, ChargeState::NotCharging),
+            (0, ChargeState::Unknown),
+            (7, ChargeState::Unknown),
+        ];
+        for (code, state) in cases {
+            assert_eq!(properties([("State", Value::from(code as u32))]).state(), state, "State {}", code);
+        }
+        assert_eq!(properties([]).state(), ChargeState::Unknown);
+    }
+
+    #[test]
+    fn maps_reading_properties() {
+        let reading = properties([
+            ("Percentage", Value::from(41.6)),
+            ("State", Value::from(2u32)),
+            ("Energy", Value::from(20.5)),
+            ("EnergyFull", Value::from(50.0)),
+            ("EnergyRate", Value::from(-8.25)),
+            ("Voltage", Value::from(0.0)),
+            ("TimeToEmpty", Value::from(5400i64)),
+            ("TimeToFull", Value::from(0i64)),
+        ])
+        .reading("BAT0".to_string());
+
+        assert_eq!(reading.percentage, 42);
+        assert_eq!(reading.state, ChargeState::Discharging);
+        assert_eq!(reading.energy, Some(20.5));
+        assert_eq!(reading.energy_full, Some(50.0));
+        assert_eq!(reading.energy_rate, Some(8.25));
+        // UPower 用 0 表示未知
+        assert_eq!(reading.voltage, None);
+        assert_eq!(reading.time_to_empty, Some(Duration::from_secs(5400)));
+        assert_eq!(reading.time_to_full, None);
+    }
+
+    #[test]
+    fn clamps_percentage() {
+        for (value, expected) in [(120.0, 100), (-3.0, 0), (99.5, 100)] {
+            let reading = properties([("Percentage", Value::from(value))]).reading("BAT0".to_string());
+            assert_eq!(reading.percentage, expected, "Percentage {}", value);
+        }
+        assert_eq!(properties([]).reading("BAT0".to_string()).percentage, 0);
+    }
+
+    #[test]
+    fn filters_batteries_and_peripherals() {
+        let battery = properties([("Type", Value::from(TYPE_BATTERY)), ("PowerSupply", Value::from(true))]);
+        let mouse = properties([("Type", Value::from(5u32)), ("PowerSupply", Value::from(false))]);
+        let line_power = properties([("Type", Value::from(TYPE_LINE_POWER)), ("PowerSupply", Value::from(false))]);
+        let absent = properties([
+            ("Type", Value::from(TYPE_BATTERY)),
+            ("PowerSupply", Value::from(true)),
+            ("IsPresent", Value::from(false)),
+        ]);
+
+        assert!(battery.is_wanted(false) && !battery.is_wanted(true));
+        assert!(mouse.is_wanted(true) && !mouse.is_wanted(false));
+        assert!(!line_power.is_wanted(true) && !line_power.is_wanted(false));
+        assert!(!absent.is_wanted(false));
+    }
+
+    #[test]
+    fn names_devices_by_native_path() {
+        let device = properties([("NativePath", Value::from("/sys/class/power_supply/BAT1"))]);
+        assert_eq!(device.name().as_deref(), Some("BAT1"));
+        assert_eq!(properties([("NativePath", Value::from(""))]).name(), None);
+    }
+}
@@ -0,0 +1,289 @@
+#[cfg(target_os = "linux")]
+mod uevent;
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::settings::duration_secs;
+
+/// 等待期间检查 stop 的间隔
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 电池监视器的轮询配置
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MonitorConfig {
+    /// 无法监听系统事件时的轮询间隔
+    #[serde(rename = "poll_interval_secs", with = "duration_secs")]
+    pub poll_interval: Duration,
+    /// 监听系统事件时的兜底轮询间隔，用于捕捉不产生事件的功率等变化
+    #[serde(rename = "event_fallback_interval_secs", with = "duration_secs")]
+    pub event_fallback_interval: Duration,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1),
+            event_fallback_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+impl MonitorConfig {
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.poll_interval >= Duration::from_millis(100),
+            "monitor.poll_interval_secs must be at least 0.1"
+        );
+        ensure!(
+            self.event_fallback_interval >= self.poll_interval,
+            "monitor.event_fallback_interval_secs must not be shorter than monitor.poll_interval_secs"
+        );
+        Ok(())
+    }
+}
+
+/// 等待电池数据可能发生变化的时机：Linux 上监听 power_supply 的 uevent，
+/// 或由数据源提供变化通知（如 UPower 的信号），其余情况定时轮询
+pub struct ChangeWatcher {
+    #[cfg(target_os = "linux")]
+    uevent: Option<uevent::UeventSocket>,
+    events: Option<Receiver<()>>,
+    interval: Duration,
+    /// 事件源失效、退回轮询时使用的间隔
+    poll_interval: Duration,
+}
+
+impl ChangeWatcher {
+    /// 收到事件后继续等待的时间，合并插拔电源时连续产生的多个事件
+    const DEBOUNCE: Duration = Duration::from_millis(200);
+
+    /// 只按固定间隔轮询
+    pub fn polling(interval: Duration) -> Self {
+        Self {
+            #[cfg(target_os = "linux")]
+            uevent: None,
+            events: None,
+            interval,
+            poll_interval: interval,
+        }
+    }
+
+    /// 数据源每次发送通知时读取，超过 event_fallback_interval 没有通知时也读取一次
+    pub fn from_events(events: Receiver<()>, config: &MonitorConfig) -> Self {
+        Self {
+            events: Some(events),
+            interval: config.event_fallback_interval,
+            ..Self::polling(config.poll_interval)
+        }
+    }
+
+    /// 尽量使用系统事件，不可用时退回轮询
+    pub fn new(config: &MonitorConfig) -> Self {
+        #[cfg(target_os = "linux")]
+        match uevent::UeventSocket::open() {
+            Ok(socket) => {
+                return Self {
+                    uevent: Some(socket),
+                    interval: config.event_fallback_interval,
+                    ..Self::polling(config.poll_interval)
+                }
+            }
+            Err(e) => eprintln!("Failed to listen for power_supply uevents, polling instead: {}", e),
+        }
+
+        Self::polling(config.poll_interval)
+    }
+
+    /// 阻塞直到收到事件或超过轮询间隔，stop 返回 true 时提前结束
+    pub fn wait(&mut self, stop: &dyn Fn() -> bool) {
+        let mut remaining = self.interval;
+        while !remaining.is_zero() && !stop() {
+            let step = remaining.min(CHECK_INTERVAL);
+            if self.wait_for_event(step) {
+                return;
+            }
+            // 事件源失效后按轮询间隔等待
+            remaining = (remaining - step).min(self.interval);
+        }
+    }
+
+    /// 最多等待 timeout，收到事件时返回 true
+    fn wait_for_event(&mut self, timeout: Duration) -> bool {
+        if let Some(events) = &self.events {
+            match events.recv_timeout(timeout) {
+                Ok(()) => {
+                    while events.recv_timeout(Self::DEBOUNCE).is_ok() {}
+                    return true;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    eprintln!("Change notifications stopped, polling instead");
+                    self.events = None;
+                    self.interval = self.poll_interval;
+                }
+            }
+            return false;
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(socket) = &self.uevent {
+            match socket.wait(timeout) {
+                Ok(true) => {
+                    while let Ok(true) = socket.wait(Self::DEBOUNCE) {}
+                    return true;
+                }
+                Ok(false) => {}
+                // 接收队列溢出，事件已经丢失：重新打开套接字，并当作收到事件立即读取
+                Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => match uevent::UeventSocket::open() {
+                    Ok(socket) => {
+                        self.uevent = Some(socket);
+                        return true;
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to reopen uevent socket, polling instead: {}", e);
+                        self.uevent = None;
+                        self.interval = self.poll_interval;
+                    }
+                },
+                Err(e) => {
+                    eprintln!("Failed to read uevent, polling instead: {}", e);
+                    self.uevent = None;
+                    self.interval = self.poll_interval;
+                }
+            }
+            return false;
+        }
+
+        thread::sleep(timeout);
+        false
+    }
+}
+
+/// 请求监视器立即重新读取，不必等到下一次事件或轮询。监视器在等待中检查请求，
+/// 最多延迟 CHECK_INTERVAL
+#[derive(Clone, Default)]
+pub struct RefreshRequest(Arc<AtomicBool>);
+
+impl RefreshRequest {
+    pub fn request(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// 是否有尚未处理的请求
+    pub fn pending(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// 取出请求，返回之前是否有请求
+    pub fn take(&self) -> bool {
+        self.0.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// 出错后的重试间隔，从 initial 开始每次翻倍，最长为 MAX
+pub struct Backoff {
+    initial: Duration,
+    next: Duration,
+}
+
+impl Backoff {
+    const MAX: Duration = Duration::from_secs(5 * 60);
+
+    pub fn new(initial: Duration) -> Self {
+        Self { initial, next: initial }
+    }
+
+    /// 操作成功后恢复到最短间隔
+    pub fn reset(&mut self) {
+        self.next = self.initial;
+    }
+
+    /// 等待当前间隔后将间隔翻倍，stop 返回 true 时提前结束
+    pub fn wait(&mut self, stop: &dyn Fn() -> bool) {
+        let mut remaining = self.next;
+        self.next = (self.next * 2).min(Self::MAX);
+        while !remaining.is_zero() && !stop() {
+            let step = remaining.min(CHECK_INTERVAL);
+            thread::sleep(step);
+            remaining -= step;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Instant};
+
+    use super::*;
+
+    fn config(poll_interval: u64, event_fallback_interval: u64) -> MonitorConfig {
+        MonitorConfig {
+            poll_interval: Duration::from_millis(poll_interval),
+            event_fallback_interval: Duration::from_millis(event_fallback_interval),
+        }
+    }
+
+    #[test]
+    fn wait_returns_after_debouncing_events() {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = ChangeWatcher::from_events(rx, &config(100, 10_000));
+        let sender = thread::spawn(move || {
+            for _ in 0..3 {
+                tx.send(()).unwrap();
+                thread::sleep(Duration::from_millis(50));
+            }
+            // 保持通道打开，直到 wait 结束
+            thread::sleep(Duration::from_millis(500));
+        });
+
+        let start = Instant::now();
+        watcher.wait(&|| false);
+        let elapsed = start.elapsed();
+        // 连续的三个事件合并为一次，最后一个之后再等待 DEBOUNCE
+        assert!(elapsed >= Duration::from_millis(100) + ChangeWatcher::DEBOUNCE, "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+        sender.join().unwrap();
+    }
+
+    #[test]
+    fn wait_times_out_without_events() {
+        let (_tx, rx) = mpsc::channel();
+        let mut watcher = ChangeWatcher::from_events(rx, &config(100, 300));
+        let start = Instant::now();
+        watcher.wait(&|| false);
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(300) && elapsed < Duration::from_secs(2), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn falls_back_to_the_poll_interval_when_events_stop() {
+        let (tx, rx) = mpsc::channel();
+        drop(tx);
+        let mut watcher = ChangeWatcher::from_events(rx, &config(100, 30_000));
+        let start = Instant::now();
+        watcher.wait(&|| false);
+        assert!(start.elapsed() < Duration::from_secs(2), "{:?}", start.elapsed());
+        assert!(watcher.events.is_none());
+        assert_eq!(watcher.interval, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn stop_interrupts_wait() {
+        let (_tx, rx) = mpsc::channel();
+        let mut watcher = ChangeWatcher::from_events(rx, &config(100, 30_000));
+        let start = Instant::now();
+        watcher.wait(&|| true);
+        assert!(start.elapsed() < Duration::from_millis(100), "{:?}", start.elapsed());
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    time::{Duration, Instant},
+};
+
+/// 监听内核 uevent 广播的 netlink 套接字
+pub struct UeventSocket {
+    fd: OwnedFd,
+}
+
+impl UeventSocket {
+    /// 内核 uevent 的多播组
+    const KERNEL_GROUP: u32 = 1;
+
+    pub fn open() -> io::Result<Self> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_KOBJECT_UEVENT,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = Self::KERNEL_GROUP;
+        let ret = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self { fd })
+    }
+
+    /// 等待 power_supply 子系统的事件，超时返回 false，其他子系统的事件会被忽略
+    pub fn wait(&self, timeout: Duration) -> io::Result<bool> {
+        let deadline = Instant::now() + timeout;
+        let mut buf = [0u8; 8192];
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let mut pollfd = libc::pollfd {
+                fd: self.fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let timeout_ms = remaining.as_millis().min(i32::MAX as u128) as libc::c_int;
+            let ret = unsafe { libc::poll(&mut pollfd, 1, timeout_ms) };
+            if ret < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+            if ret == 0 {
+                return Ok(false);
+            }
+
+            let len = unsafe {
+                libc::recv(self.fd.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0)
+            };
+            if len < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if is_power_supply_event(&buf[..len as usize]) {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+/// uevent 消息由 NUL 分隔的 KEY=VALUE 字段组成
+fn is_power_supply_event(message: &[u8]) -> bool {
+    message
+        .split(|&b| b == 0)
+        .any(|field| field == b"SUBSYSTEM=power_supply")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_power_supply_events() {
+        let power_supply = b"change@/devices/LNXSYSTM:00/PNP0C0A:00/power_supply/BAT0\0ACTION=change\0\
+            SUBSYSTEM=power_supply\0POWER_SUPPLY_NAME=BAT0\0";
+        assert!(is_power_supply_event(power_supply));
+
+        let usb = b"add@/devices/pci0000:00/usb1/1-1\0ACTION=add\0SUBSYSTEM=usb\0DEVTYPE=usb_device\0";
+        assert!(!is_power_supply_event(usb));
+        // 只匹配完整的字段
+        assert!(!is_power_supply_event(b"ACTION=change\0SUBSYSTEM=power_supply_extra\0"));
+        assert!(!is_power_supply_event(b"POWER_SUPPLY_NAME=SUBSYSTEM=power_supply"));
+        assert!(!is_power_supply_event(b""));
+    }
+}
@@ -0,0 +1,274 @@
+use std::path::PathBuf;
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::{
+    battery_icon_generator::BatteryIconGenerator,
+    battery_source::{BatteryReading, ChargeState, SourceKind},
+    estimator::TimeLeft,
+    icon_style::{Appearance, IconMode, IconStyle},
+    settings::{Settings, SettingsStore},
+    status::StatusReport,
+    template::{describe_reading, Template, TemplateContext},
+};
+
+pub const USAGE: &str = "\
+Usage:
+  percentage-rust                 Run the tray app
+  percentage-rust status [--json]
+      Print the current battery readings and exit
+  percentage-rust render --pct <0-100> -o <file.png> [options]
+      Render a tray icon to an image file
+      --charging            Same as --state charging
+      --state <state>       charging, discharging, full, empty, not-charging or unknown
+      --watts <W>           Power draw, used by the power mode and templates
+      --mode <mode>         text, glyph or power
+      --theme <theme>       light or dark (default: light)
+      --size <px>           Icon size in pixels (default: 64)
+      --settings <file>     Take the icon style from a settings file instead of the defaults";
+
+/// 命令行子命令
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Help,
+    Status { json: bool },
+    Render(RenderOptions),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    pub percentage: u32,
+    pub state: ChargeState,
+    pub watts: Option<f32>,
+    pub mode: Option<IconMode>,
+    pub appearance: Appearance,
+    pub size: u32,
+    pub settings: Option<PathBuf>,
+    pub output: PathBuf,
+}
+
+/// 解析命令行参数（不含程序名），没有子命令时返回 None，以托盘模式运行
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Command>> {
+    let mut args = args.into_iter();
+    let Some(command) = args.next() else {
+        return Ok(None);
+    };
+    match command.as_str() {
+        // macOS 从 Finder 启动时会附带进程序列号参数
+        arg if arg.starts_with("-psn_") => Ok(None),
+        "help" | "-h" | "--help" => Ok(Some(Command::Help)),
+        "status" => parse_status(args).map(Some),
+        "render" => parse_render(args).map(Some),
+        other => bail!("Unknown command: {}", other),
+    }
+}
+
+fn parse_status(args: impl Iterator<Item = String>) -> Result<Command> {
+    let mut json = false;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            other => bail!("Unknown option for status: {}", other),
+        }
+    }
+    Ok(Command::Status { json })
+}
+
+fn parse_render(mut args: impl Iterator<Item = String>) -> Result<Command> {
+    let mut percentage = None;
+    let mut output = None;
+    let mut options = RenderOptions {
+        percentage: 0,
+        state: ChargeState::Discharging,
+        watts: None,
+        mode: None,
+        appearance: Appearance::Light,
+        size: 64,
+        settings: None,
+        output: PathBuf::new(),
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().with_context(|| format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--pct" => {
+                let pct: u32 = value()?.parse().context("Invalid --pct")?;
+                ensure!(pct <= 100, "--pct must be between 0 and 100");
+                percentage = Some(pct);
+            }
+            "--charging" => options.state = ChargeState::Charging,
+            "--state" => options.state = value()?.parse()?,
+            "--watts" => options.watts = Some(value()?.parse().context("Invalid --watts")?),
+            "--mode" => options.mode = Some(value()?.parse()?),
+            "--theme" => {
+                options.appearance = match value()?.as_str() {
+                    "light" => Appearance::Light,
+                    "dark" => Appearance::Dark,
+                    other => bail!("Unknown theme: {}", other),
+                }
+            }
+            "--size" => {
+                let size: u32 = value()?.parse().context("Invalid --size")?;
+                ensure!((16..=256).contains(&size), "--size must be between 16 and 256");
+                options.size = size;
+            }
+            "--settings" => options.settings = Some(PathBuf::from(value()?)),
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            other => bail!("Unknown option for render: {}", other),
+        }
+    }
+
+    options.percentage = percentage.context("render requires --pct")?;
+    options.output = output.context("render requires -o <file>")?;
+    Ok(Command::Render(options))
+}
+
+/// Windows 发布版本是 GUI 程序，没有控制台。从终端运行子命令时连接到父进程的控制台，
+/// 输出才能显示出来；从资源管理器启动时没有父控制台，连接失败，输出被丢弃
+#[cfg(windows)]
+pub fn attach_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+pub fn attach_console() {}
+
+pub fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Command::Status { json } => status(json),
+        Command::Render(options) => render(&options),
+    }
+}
+
+/// 读取一次电池数据并打印，使用与托盘相同的数据源与汇总方式
+fn status(json: bool) -> Result<()> {
+    let settings = Settings::load_or_default(&SettingsStore::default_path()?)?;
+    let kind = SourceKind::from_env()?.unwrap_or(settings.source.clone());
+    let batteries = kind.open()?.poll()?;
+
+    if json {
+        let status = StatusReport::new(&batteries, settings.aggregation);
+        println!("{}", serde_json::to_string_pretty(&status).context("Failed to serialize status")?);
+        return Ok(());
+    }
+
+    let Some(headline) = settings.aggregation.headline(&batteries) else {
+        bail!("No battery found");
+    };
+    let details: Template = "{details}".parse()?;
+    let mut readings = vec![&headline];
+    if batteries.len() > 1 {
+        readings.extend(&batteries);
+    }
+    for reading in readings {
+        let context = TemplateContext {
+            reading,
+            time_left: TimeLeft::reported(reading),
+        };
+        let mut line = format!("{}: {}", reading.name, describe_reading(reading));
+        if let Some(time_left) = context.time_left {
+            line.push_str(&format!(", {}", time_left));
+        }
+        let details = details.render(&context);
+        if !details.is_empty() {
+            line.push_str(&format!(" ({})", details));
+        }
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// 用托盘的图标生成器渲染一张图标并保存，格式由文件扩展名决定
+fn render(options: &RenderOptions) -> Result<()> {
+    let mut style = match &options.settings {
+        Some(path) => Settings::load_or_default(path)?.icon,
+        None => IconStyle::default(),
+    };
+    if let Some(mode) = options.mode {
+        style.mode = mode;
+    }
+    let theme = style.resolve_theme(options.appearance);
+
+    let mut generator = BatteryIconGenerator::default();
+    if let Err(e) = generator.set_style(style) {
+        eprintln!("{:#}", e);
+    }
+    generator.set_theme(theme);
+    generator.set_size(options.size);
+
+    let mut reading = BatteryReading::new("Battery", options.percentage, options.state);
+    reading.energy_rate = options.watts;
+    let context = TemplateContext {
+        reading: &reading,
+        time_left: None,
+    };
+    let text = generator.icon_text(std::slice::from_ref(&context));
+    let image = generator.render_image(&[(options.percentage, options.state)], &text)?;
+    image
+        .save(&options.output)
+        .with_context(|| format!("Failed to write {}", options.output.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::Path};
+
+    use super::*;
+
+    /// 与 testdata 中的图标逐像素比较。修改绘制逻辑后，
+    /// 设置环境变量 UPDATE_GOLDEN=1 运行测试重新生成
+    #[test]
+    fn render_matches_golden_images() {
+        let cases = [
+            ("render-42-charging.png", "--pct 42 --charging"),
+            ("render-7-glyph-dark.png", "--pct 7 --mode glyph --theme dark"),
+            ("render-80-power.png", "--pct 80 --mode power --watts 12.5 --size 32"),
+        ];
+        for (name, args) in cases {
+            let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata").join(name);
+            let output = env::temp_dir().join(format!("percentage-rust-{}-{}", std::process::id(), name));
+            let args = ["render"]
+                .into_iter()
+                .chain(args.split(' '))
+                .map(String::from)
+                .chain(["-o".to_string(), output.to_string_lossy().into_owned()]);
+            let Some(Command::Render(options)) = parse(args).unwrap() else {
+                panic!("expected a render command");
+            };
+            render(&options).unwrap();
+
+            if env::var_os("UPDATE_GOLDEN").is_some() {
+                std::fs::copy(&output, &golden).unwrap();
+            }
+            let actual = image::open(&output).unwrap().into_rgba8();
+            let expected = image::open(&golden).unwrap().into_rgba8();
+            assert!(actual == expected, "{} differs from the golden image", name);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_invalid_render_options() {
+        let invalid = [
+            "render -o icon.png",
+            "render --pct 101 -o icon.png",
+            "render --pct 42",
+            "render --pct 42 --size 8 -o icon.png",
+        ];
+        for args in invalid {
+            assert!(parse(args.split(' ').map(String::from)).is_err(), "{}", args);
+        }
+        assert_eq!(parse(Vec::new()).unwrap(), None);
+    }
+}
@@ -0,0 +1,60 @@
+use std::sync::Arc;
+use anyhow::Context;
+use tauri::{async_runtime, AppHandle, State};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::{
+    health::{HealthReport, ReportFormat},
+    history::{self, HistoryRecord, HistoryStore},
+    settings::SettingsStore,
+};
+
+/// 读取最近 hours 小时的历史记录，供历史图表窗口使用。文件可能较大，在阻塞线程中读取
+#[tauri::command]
+pub async fn get_history(history: State<'_, Arc<HistoryStore>>, hours: u32) -> Result<Vec<HistoryRecord>, String> {
+    let history = Arc::clone(&history);
+    let since = history::unix_now().saturating_sub(hours as u64 * 3600);
+    async_runtime::spawn_blocking(move || history.load(since))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// 读取当前数据源的电池健康报告，读取数据源可能阻塞，在阻塞线程中进行
+#[tauri::command]
+pub async fn get_health_report(settings: State<'_, Arc<SettingsStore>>) -> Result<HealthReport, String> {
+    let source = settings.source();
+    async_runtime::spawn_blocking(move || HealthReport::collect(&source))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// 导出电池健康报告到文档目录，HTML 报告导出后用默认浏览器打开，返回文件路径
+#[tauri::command]
+pub async fn export_health_report(
+    app: AppHandle,
+    settings: State<'_, Arc<SettingsStore>>,
+    format: String,
+) -> Result<String, String> {
+    let source = settings.source();
+    let export = move || -> anyhow::Result<String> {
+        let format: ReportFormat = format.parse()?;
+        let dir = dirs::document_dir()
+            .or_else(dirs::home_dir)
+            .context("Failed to locate documents directory")?;
+        let report = HealthReport::collect(&source)?;
+        let path = report.export(&dir, format)?;
+        let path = path.to_string_lossy().into_owned();
+        if format == ReportFormat::Html {
+            app.opener()
+                .open_path(path.clone(), None::<&str>)
+                .context("Failed to open report")?;
+        }
+        Ok(path)
+    };
+    async_runtime::spawn_blocking(export)
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
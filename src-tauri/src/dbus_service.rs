@@ -0,0 +1,198 @@
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use tauri::async_runtime;
+use tokio::sync::watch;
+use zbus::{connection, fdo, interface};
+
+use crate::{
+    battery_source::{ChargeState, SourceUpdate},
+    change_watcher::RefreshRequest,
+    estimator::{TimeEstimator, TimeLeft},
+    icon_style::IconMode,
+    settings::{Settings, SettingsStore},
+};
+
+/// 会话总线上的服务名与对象路径
+pub const BUS_NAME: &str = "com.percentage_rust.Battery";
+pub const OBJECT_PATH: &str = "/com/percentage_rust/Battery";
+
+/// 通过 D-Bus 公开的属性值，与上次不同的属性会发出 PropertiesChanged
+#[derive(Debug, Clone, PartialEq)]
+struct Snapshot {
+    percentage: u32,
+    state: ChargeState,
+    /// 剩余时间（秒），未知时为 0，与 UPower 的约定一致
+    time_to_empty: i64,
+    time_to_full: i64,
+    display_mode: IconMode,
+}
+
+impl Snapshot {
+    /// 尚未读到数据或数据源出错时，电量为 0、状态为 unknown。剩余时间优先使用系统报告的值，
+    /// 不报告时（如多块电池汇总后）由 estimator 推算；只有 updated 为 true 即读数有更新时才计入估算
+    fn new(
+        estimator: &mut TimeEstimator,
+        update: Option<&SourceUpdate>,
+        settings: &Settings,
+        updated: bool,
+    ) -> Self {
+        let headline = match update {
+            Some(SourceUpdate::Readings(batteries)) => settings.aggregation.headline(batteries),
+            _ => None,
+        };
+        if let (Some(headline), true) = (&headline, updated) {
+            estimator.record(headline);
+        }
+        let (time_to_empty, time_to_full) = match headline.as_ref().and_then(|h| estimator.estimate(h)) {
+            Some(TimeLeft::ToEmpty(time)) => (time.as_secs() as i64, 0),
+            Some(TimeLeft::ToFull(time)) => (0, time.as_secs() as i64),
+            None => (0, 0),
+        };
+        Self {
+            percentage: headline.as_ref().map_or(0, |h| h.percentage),
+            state: headline.as_ref().map_or(ChargeState::Unknown, |h| h.state),
+            time_to_empty,
+            time_to_full,
+            display_mode: settings.icon.mode,
+        }
+    }
+}
+
+/// com.percentage_rust.Battery 接口
+struct BatteryService {
+    snapshot: Snapshot,
+    settings: Arc<SettingsStore>,
+    refresh: RefreshRequest,
+}
+
+#[interface(name = "com.percentage_rust.Battery")]
+impl BatteryService {
+    /// 按设置的汇总方式得到的电量（0-100）
+    #[zbus(property)]
+    fn percentage(&self) -> u32 {
+        self.snapshot.percentage
+    }
+
+    /// 充电状态，如 charging、discharging、not-charging
+    #[zbus(property)]
+    fn state(&self) -> String {
+        self.snapshot.state.as_str().to_string()
+    }
+
+    #[zbus(property)]
+    fn time_to_empty(&self) -> i64 {
+        self.snapshot.time_to_empty
+    }
+
+    #[zbus(property)]
+    fn time_to_full(&self) -> i64 {
+        self.snapshot.time_to_full
+    }
+
+    /// 托盘图标的显示模式：text、glyph 或 power
+    #[zbus(property)]
+    fn display_mode(&self) -> String {
+        self.snapshot.display_mode.as_str().to_string()
+    }
+
+    /// 让监视器立即读取一次电池，读数变化时属性随之更新
+    fn refresh(&self) {
+        self.refresh.request();
+    }
+
+    /// 修改托盘图标的显示模式并写入设置。写文件可能阻塞，在阻塞线程中进行
+    async fn set_display_mode(&self, mode: &str) -> fdo::Result<()> {
+        let mode: IconMode = mode
+            .parse()
+            .map_err(|e: anyhow::Error| fdo::Error::InvalidArgs(e.to_string()))?;
+        let settings = self.settings.clone();
+        async_runtime::spawn_blocking(move || settings.update(|settings| settings.icon.mode = mode))
+            .await
+            .map_err(|e| fdo::Error::Failed(e.to_string()))?
+            .map_err(|e| fdo::Error::Failed(format!("{:#}", e)))
+    }
+}
+
+/// 在会话总线上注册服务，读数或设置变化时更新属性并发出 PropertiesChanged。
+/// Refresh 方法通过 refresh 唤醒电池监视器
+pub async fn run_dbus_service(
+    settings: Arc<SettingsStore>,
+    mut updates_rx: watch::Receiver<Option<SourceUpdate>>,
+    refresh: RefreshRequest,
+) -> Result<()> {
+    let mut settings_rx = settings.subscribe();
+    let mut estimator = TimeEstimator::default();
+    let service = BatteryService {
+        snapshot: Snapshot::new(
+            &mut estimator,
+            updates_rx.borrow_and_update().as_ref(),
+            &settings_rx.borrow_and_update(),
+            true,
+        ),
+        settings,
+        refresh,
+    };
+
+    let connection = connection::Builder::session()
+        .and_then(|builder| builder.name(BUS_NAME))
+        .and_then(|builder| builder.serve_at(OBJECT_PATH, service))
+        .context("Failed to configure D-Bus connection")?
+        .build()
+        .await
+        .with_context(|| format!("Failed to register {} on the session bus", BUS_NAME))?;
+    let iface_ref = connection
+        .object_server()
+        .interface::<_, BatteryService>(OBJECT_PATH)
+        .await
+        .context("Failed to look up D-Bus interface")?;
+
+    loop {
+        let updated = tokio::select! {
+            changed = updates_rx.changed() => {
+                if changed.is_err() {
+                    return Ok(());
+                }
+                true
+            }
+            changed = settings_rx.changed() => {
+                if changed.is_err() {
+                    return Ok(());
+                }
+                false
+            }
+        };
+
+        let snapshot = Snapshot::new(
+            &mut estimator,
+            updates_rx.borrow_and_update().as_ref(),
+            &settings_rx.borrow_and_update(),
+            updated,
+        );
+        let mut iface = iface_ref.get_mut().await;
+        let old = std::mem::replace(&mut iface.snapshot, snapshot);
+        let new = &iface.snapshot;
+        let emitter = iface_ref.signal_emitter();
+        if old.percentage != new.percentage {
+            log_emit_error(iface.percentage_changed(emitter).await);
+        }
+        if old.state != new.state {
+            log_emit_error(iface.state_changed(emitter).await);
+        }
+        if old.time_to_empty != new.time_to_empty {
+            log_emit_error(iface.time_to_empty_changed(emitter).await);
+        }
+        if old.time_to_full != new.time_to_full {
+            log_emit_error(iface.time_to_full_changed(emitter).await);
+        }
+        if old.display_mode != new.display_mode {
+            log_emit_error(iface.display_mode_changed(emitter).await);
+        }
+    }
+}
+
+/// 发送 PropertiesChanged 失败时只输出日志，属性值已经更新，服务继续运行
+fn log_emit_error(result: zbus::Result<()>) {
+    if let Err(e) = result {
+        eprintln!("Failed to emit D-Bus PropertiesChanged: {}", e);
+    }
+}
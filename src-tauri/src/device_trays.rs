@@ -0,0 +1,191 @@
+use std::collections::{btree_map::Entry, BTreeMap};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tauri::{
+    async_runtime,
+    menu::{Menu, MenuItem},
+    tray::{TrayIcon, TrayIconBuilder},
+    AppHandle, Wry,
+};
+use tokio::sync::watch;
+
+use crate::{
+    battery_icon_generator::BatteryIconGenerator,
+    battery_source::{BatteryReading, SourceUpdate},
+    estimator::TimeLeft,
+    icon_style::{Appearance, IconStyle},
+    settings::Settings,
+    template::TemplateContext,
+    tray_updater,
+};
+
+/// 按设备显示托盘图标的设置
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrayConfig {
+    /// 除主图标外，为每块电池和每个外设各显示一个托盘图标
+    pub per_device: bool,
+    /// 按设备名称或外设型号指定图标样式，未列出的设备使用 [icon] 的样式
+    pub styles: BTreeMap<String, IconStyle>,
+}
+
+impl TrayConfig {
+    pub fn validate(&self) -> Result<()> {
+        for (name, style) in &self.styles {
+            style
+                .validate()
+                .with_context(|| format!("Invalid tray.styles.{:?}", name))?;
+        }
+        Ok(())
+    }
+}
+
+/// 一个设备的托盘图标，各自使用独立的生成器，样式与缓存互不影响
+struct DeviceTray {
+    tray: TrayIcon,
+    /// 菜单中唯一的一项，显示设备状态。Linux 的 AppIndicator 不显示没有菜单的图标
+    status_item: MenuItem<Wry>,
+    icon_generator: BatteryIconGenerator,
+}
+
+/// 按设备显示的托盘图标，以设备名称为键
+struct DeviceTrays {
+    app: AppHandle,
+    trays: BTreeMap<String, DeviceTray>,
+}
+
+/// 启动异步任务维护按设备显示的托盘图标：设备出现时创建图标，消失或关闭设置时移除
+pub fn spawn_device_trays(
+    app: AppHandle,
+    mut rx: watch::Receiver<Option<SourceUpdate>>,
+    mut peripherals_rx: watch::Receiver<Option<SourceUpdate>>,
+    mut settings_rx: watch::Receiver<Settings>,
+    mut appearance_rx: watch::Receiver<Appearance>,
+) {
+    async_runtime::spawn(async move {
+        let mut trays = DeviceTrays {
+            app,
+            trays: BTreeMap::new(),
+        };
+
+        loop {
+            let mut devices = readings(&mut rx);
+            devices.extend(readings(&mut peripherals_rx));
+            let settings = settings_rx.borrow_and_update().clone();
+            let appearance = *appearance_rx.borrow_and_update();
+            trays.sync(&devices, &settings, appearance).await;
+
+            tokio::select! {
+                changed = rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+                changed = peripherals_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+                changed = settings_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+                changed = appearance_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// 监视器的最新读数，尚未读到数据或数据源出错时为空
+fn readings(rx: &mut watch::Receiver<Option<SourceUpdate>>) -> Vec<BatteryReading> {
+    match &*rx.borrow_and_update() {
+        Some(SourceUpdate::Readings(readings)) => readings.clone(),
+        _ => Vec::new(),
+    }
+}
+
+fn tray_id(name: &str) -> String {
+    format!("device:{}", name)
+}
+
+impl DeviceTrays {
+    /// 按当前的设备列表创建、更新和移除图标
+    async fn sync(&mut self, devices: &[BatteryReading], settings: &Settings, appearance: Appearance) {
+        let devices: &[BatteryReading] = if settings.tray.per_device { devices } else { &[] };
+
+        let stale: Vec<String> = self
+            .trays
+            .keys()
+            .filter(|name| !devices.iter().any(|device| &device.name == *name))
+            .cloned()
+            .collect();
+        for name in stale {
+            self.trays.remove(&name);
+            self.app.remove_tray_by_id(tray_id(&name).as_str());
+        }
+
+        for device in devices {
+            if let Err(e) = self.show(device, settings, appearance).await {
+                eprintln!("Failed to update tray icon of {}: {:#}", device.label(), e);
+            }
+        }
+    }
+
+    /// 用设备自己的样式绘制图标，提示使用单块电池的模板
+    async fn show(&mut self, device: &BatteryReading, settings: &Settings, appearance: Appearance) -> Result<()> {
+        let tray = match self.trays.entry(device.name.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let status_item = MenuItem::new(&self.app, device.label(), false, None::<&str>)
+                    .context("Failed to create tray menu")?;
+                let menu = Menu::with_items(&self.app, &[&status_item]).context("Failed to create tray menu")?;
+                let tray = TrayIconBuilder::with_id(tray_id(&device.name))
+                    .menu(&menu)
+                    .build(&self.app)
+                    .context("Failed to create tray icon")?;
+                entry.insert(DeviceTray {
+                    tray,
+                    status_item,
+                    icon_generator: BatteryIconGenerator::default(),
+                })
+            }
+        };
+
+        let styles = &settings.tray.styles;
+        let style = styles
+            .get(&device.name)
+            .or_else(|| styles.get(device.model.as_ref()?))
+            .unwrap_or(&settings.icon);
+        let size = style.size.unwrap_or_else(|| tray_updater::tray_size(&self.app));
+        tray.icon_generator.set_theme(style.resolve_theme(appearance));
+        tray.icon_generator.set_size(size);
+        // 字体加载失败时生成器已改用内置字体，只记录错误
+        if let Err(e) = tray.icon_generator.set_style(style.clone()) {
+            eprintln!("{:#}", e);
+        }
+
+        let context = TemplateContext {
+            reading: device,
+            time_left: TimeLeft::reported(device),
+        };
+        let text = tray.icon_generator.icon_text(std::slice::from_ref(&context));
+        let icon = tray
+            .icon_generator
+            .generate_icon(&[(device.percentage, device.state)], &text)
+            .await?;
+        tray.tray.set_icon(Some(icon)).context("Failed to set tray icon")?;
+        let tooltip = settings.tooltip.battery.render(&context);
+        tray.status_item
+            .set_text(&tooltip)
+            .context("Failed to update tray menu")?;
+        tray.tray
+            .set_tooltip(Some(tooltip))
+            .context("Failed to set tray tooltip")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::battery_source::{BatteryReading, ChargeState};
+
+/// 剩余时间估算结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeLeft {
+    /// 距离耗尽
+    ToEmpty(Duration),
+    /// 距离充满
+    ToFull(Duration),
+}
+
+impl TimeLeft {
+    pub fn duration(&self) -> Duration {
+        match self {
+            TimeLeft::ToEmpty(d) | TimeLeft::ToFull(d) => *d,
+        }
+    }
+
+    /// 系统报告的剩余时间，不做估算
+    pub fn reported(reading: &BatteryReading) -> Option<TimeLeft> {
+        match reading.state {
+            ChargeState::Discharging => reading.time_to_empty.map(TimeLeft::ToEmpty),
+            ChargeState::Charging => reading.time_to_full.map(TimeLeft::ToFull),
+            _ => None,
+        }
+    }
+
+    /// 简短格式，如 `2h13m`、`45m`，适合放在图标上
+    pub fn short(&self) -> String {
+        let (hours, minutes) = self.hours_minutes();
+        if hours > 0 {
+            format!("{}h{:02}m", hours, minutes)
+        } else {
+            format!("{}m", minutes)
+        }
+    }
+
+    /// 四舍五入到分钟后的小时数和分钟数
+    fn hours_minutes(&self) -> (u64, u64) {
+        let minutes = (self.duration().as_secs() + 30) / 60;
+        (minutes / 60, minutes % 60)
+    }
+}
+
+impl fmt::Display for TimeLeft {
+    /// 格式如 `2h 13m remaining`、`45m until full`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (hours, minutes) = self.hours_minutes();
+        if hours > 0 {
+            write!(f, "{}h {:02}m", hours, minutes)?;
+        } else {
+            write!(f, "{}m", minutes)?;
+        }
+        match self {
+            TimeLeft::ToEmpty(_) => write!(f, " remaining"),
+            TimeLeft::ToFull(_) => write!(f, " until full"),
+        }
+    }
+}
+
+struct Sample {
+    at: Instant,
+    energy: Option<f32>,
+    energy_rate: Option<f32>,
+}
+
+/// 剩余时间估算器，在系统不报告剩余时间时，用最近一段时间的平均功率计算
+#[derive(Default)]
+pub struct TimeEstimator {
+    samples: VecDeque<Sample>,
+    /// 当前窗口对应的电池与状态，变化时清空窗口
+    key: Option<(String, ChargeState)>,
+}
+
+impl TimeEstimator {
+    /// 平均功率的时间窗口
+    const WINDOW: Duration = Duration::from_secs(10 * 60);
+    /// 只有能量数据时，至少需要跨越这么长时间才能计算功率
+    const MIN_SPAN: Duration = Duration::from_secs(60);
+    /// 超过此值的估算视为不可靠
+    const MAX_ESTIMATE: Duration = Duration::from_secs(48 * 3600);
+
+    /// 记录一个读数，电池或充放电状态变化时重新开始统计
+    pub fn record(&mut self, reading: &BatteryReading) {
+        self.record_at(reading, Instant::now());
+    }
+
+    fn record_at(&mut self, reading: &BatteryReading, now: Instant) {
+        let key = (reading.name.clone(), reading.state);
+        if self.key.as_ref() != Some(&key) {
+            self.samples.clear();
+            self.key = Some(key);
+        }
+
+        self.samples.push_back(Sample {
+            at: now,
+            energy: reading.energy,
+            energy_rate: reading.energy_rate.filter(|&rate| rate > 0.0),
+        });
+        while let Some(oldest) = self.samples.front() {
+            if now.duration_since(oldest.at) <= Self::WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// 估算剩余时间，优先使用系统报告的值
+    pub fn estimate(&self, reading: &BatteryReading) -> Option<TimeLeft> {
+        TimeLeft::reported(reading)
+            .or_else(|| match reading.state {
+                ChargeState::Discharging => self.time_at_rate(reading.energy?).map(TimeLeft::ToEmpty),
+                ChargeState::Charging => self
+                    .time_at_rate(reading.energy_full? - reading.energy?)
+                    .map(TimeLeft::ToFull),
+                _ => None,
+            })
+            .filter(|time_left| time_left.duration() <= Self::MAX_ESTIMATE)
+    }
+
+    /// 以平均功率消耗或补充指定能量（Wh）所需的时间
+    fn time_at_rate(&self, energy: f32) -> Option<Duration> {
+        let rate = self.average_rate()?;
+        if energy < 0.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f32(energy / rate * 3600.0))
+    }
+
+    /// 窗口内的平均功率（W）。有功率读数时直接平均，否则用能量变化推算
+    fn average_rate(&self) -> Option<f32> {
+        let rates: Vec<f32> = self.samples.iter().filter_map(|s| s.energy_rate).collect();
+        if !rates.is_empty() {
+            return Some(rates.iter().sum::<f32>() / rates.len() as f32);
+        }
+
+        let first = self.samples.iter().find(|s| s.energy.is_some())?;
+        let last = self.samples.iter().rev().find(|s| s.energy.is_some())?;
+        let span = last.at.duration_since(first.at);
+        if span < Self::MIN_SPAN {
+            return None;
+        }
+
+        let delta = (last.energy? - first.energy?).abs();
+        let rate = delta / span.as_secs_f32() * 3600.0;
+        (rate > 0.0).then_some(rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(state: ChargeState, energy: f32, energy_rate: Option<f32>) -> BatteryReading {
+        BatteryReading {
+            energy: Some(energy),
+            energy_full: Some(50.0),
+            energy_rate,
+            ..BatteryReading::new("BAT0", (energy / 50.0 * 100.0) as u32, state)
+        }
+    }
+
+    #[test]
+    fn prefers_reported_time() {
+        let mut estimator = TimeEstimator::default();
+        let mut battery = reading(ChargeState::Discharging, 25.0, Some(10.0));
+        battery.time_to_empty = Some(Duration::from_secs(1800));
+        estimator.record(&battery);
+        assert_eq!(estimator.estimate(&battery), Some(TimeLeft::ToEmpty(Duration::from_secs(1800))));
+    }
+
+    #[test]
+    fn uses_average_power() {
+        let mut estimator = TimeEstimator::default();
+        estimator.record(&reading(ChargeState::Discharging, 25.0, Some(8.0)));
+        let battery = reading(ChargeState::Discharging, 24.0, Some(12.0));
+        estimator.record(&battery);
+        // 24 Wh ÷ 10 W
+        assert_eq!(estimator.estimate(&battery), Some(TimeLeft::ToEmpty(Duration::from_secs(8640))));
+
+        let mut estimator = TimeEstimator::default();
+        let battery = reading(ChargeState::Charging, 30.0, Some(20.0));
+        estimator.record(&battery);
+        // 还需 20 Wh
+        assert_eq!(estimator.estimate(&battery), Some(TimeLeft::ToFull(Duration::from_secs(3600))));
+    }
+
+    #[test]
+    fn derives_power_from_energy_change() {
+        let mut estimator = TimeEstimator::default();
+        let start = Instant::now();
+        estimator.record_at(&reading(ChargeState::Discharging, 30.0, None), start);
+        let battery = reading(ChargeState::Discharging, 29.5, None);
+
+        // 跨度不足 MIN_SPAN 时不估算
+        estimator.record_at(&battery, start + Duration::from_secs(30));
+        assert_eq!(estimator.estimate(&battery), None);
+
+        // 5 分钟消耗 0.5 Wh，即 6 W
+        estimator.record_at(&battery, start + Duration::from_secs(300));
+        let Some(TimeLeft::ToEmpty(left)) = estimator.estimate(&battery) else {
+            panic!("expected an estimate");
+        };
+        assert_eq!(left.as_secs() / 60, 295);
+    }
+
+    #[test]
+    fn resets_when_state_changes() {
+        let mut estimator = TimeEstimator::default();
+        estimator.record(&reading(ChargeState::Discharging, 25.0, Some(10.0)));
+        let battery = reading(ChargeState::Charging, 25.0, None);
+        estimator.record(&battery);
+        assert_eq!(estimator.estimate(&battery), None);
+    }
+
+    #[test]
+    fn drops_samples_outside_the_window() {
+        let mut estimator = TimeEstimator::default();
+        let start = Instant::now();
+        estimator.record_at(&reading(ChargeState::Discharging, 40.0, Some(100.0)), start);
+        let battery = reading(ChargeState::Discharging, 20.0, Some(10.0));
+        estimator.record_at(&battery, start + TimeEstimator::WINDOW + Duration::from_secs(1));
+        assert_eq!(estimator.estimate(&battery), Some(TimeLeft::ToEmpty(Duration::from_secs(7200))));
+    }
+
+    #[test]
+    fn ignores_implausible_estimates() {
+        let mut estimator = TimeEstimator::default();
+        let battery = reading(ChargeState::Discharging, 50.0, Some(0.5));
+        estimator.record(&battery);
+        assert_eq!(estimator.estimate(&battery), None);
+    }
+
+    #[test]
+    fn formats_time_left() {
+        let time_left = TimeLeft::ToEmpty(Duration::from_secs(2 * 3600 + 13 * 60 + 10));
+        assert_eq!(time_left.to_string(), "2h 13m remaining");
+        assert_eq!(time_left.short(), "2h13m");
+        assert_eq!(TimeLeft::ToFull(Duration::from_secs(45 * 60)).to_string(), "45m until full");
+    }
+}
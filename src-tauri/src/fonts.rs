@@ -0,0 +1,271 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use ab_glyph::{FontArc, FontRef};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// 随程序打包的字体
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundledFont {
+    ComicMono,
+    DejaVuSansMono,
+}
+
+impl BundledFont {
+    pub const ALL: [BundledFont; 2] = [BundledFont::ComicMono, BundledFont::DejaVuSansMono];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BundledFont::ComicMono => "comic-mono",
+            BundledFont::DejaVuSansMono => "dejavu-sans-mono",
+        }
+    }
+
+    /// 托盘菜单中显示的名称
+    pub fn label(&self) -> &'static str {
+        match self {
+            BundledFont::ComicMono => "Comic Mono",
+            BundledFont::DejaVuSansMono => "DejaVu Sans Mono",
+        }
+    }
+
+    fn bytes(&self) -> &'static [u8] {
+        match self {
+            BundledFont::ComicMono => include_bytes!("../assets/ComicMono.ttf"),
+            BundledFont::DejaVuSansMono => include_bytes!("../assets/DejaVuSansMono-Bold.ttf"),
+        }
+    }
+
+    pub fn load(&self) -> FontArc {
+        let font = FontRef::try_from_slice(self.bytes()).expect("Bundled font is invalid");
+        FontArc::new(font)
+    }
+}
+
+impl FromStr for BundledFont {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match BundledFont::ALL.into_iter().find(|font| font.as_str() == s) {
+            Some(font) => Ok(font),
+            None => bail!("Unknown bundled font: {}", s),
+        }
+    }
+}
+
+/// 图标文字使用的字体，配置文件中写作 `bundled:名称`、`file:路径` 或 `family:字体族名`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum FontChoice {
+    Bundled(BundledFont),
+    /// 字体文件路径（TTF/OTF）
+    File(PathBuf),
+    /// 按字体族名在系统字体中查找
+    Family(String),
+}
+
+impl Default for FontChoice {
+    fn default() -> Self {
+        FontChoice::Bundled(BundledFont::ComicMono)
+    }
+}
+
+impl FontChoice {
+    /// 加载字体，失败时由调用方决定是否回退
+    pub fn load(&self) -> Result<FontArc> {
+        match self {
+            FontChoice::Bundled(font) => Ok(font.load()),
+            FontChoice::File(path) => load_file(path),
+            FontChoice::Family(family) => {
+                let path = find_system_font(family)?;
+                load_file(&path).with_context(|| format!("Failed to load font family {}", family))
+            }
+        }
+    }
+
+    /// 加载字体，失败时回退到内置的 Comic Mono，同时返回失败原因
+    pub fn load_or_fallback(&self) -> (FontArc, Option<anyhow::Error>) {
+        match self.load() {
+            Ok(font) => (font, None),
+            Err(e) => (BundledFont::ComicMono.load(), Some(e)),
+        }
+    }
+}
+
+impl FromStr for FontChoice {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            Some(("bundled", name)) => Ok(FontChoice::Bundled(name.parse()?)),
+            Some(("file", path)) if !path.is_empty() => Ok(FontChoice::File(PathBuf::from(path))),
+            Some(("family", family)) if !family.trim().is_empty() => {
+                Ok(FontChoice::Family(family.trim().to_string()))
+            }
+            _ => bail!("Invalid font: {} (expected bundled:<name>, file:<path> or family:<name>)", s),
+        }
+    }
+}
+
+impl fmt::Display for FontChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontChoice::Bundled(font) => write!(f, "bundled:{}", font.as_str()),
+            FontChoice::File(path) => write!(f, "file:{}", path.display()),
+            FontChoice::Family(family) => write!(f, "family:{}", family),
+        }
+    }
+}
+
+impl TryFrom<String> for FontChoice {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<FontChoice> for String {
+    fn from(choice: FontChoice) -> Self {
+        choice.to_string()
+    }
+}
+
+fn load_file(path: &Path) -> Result<FontArc> {
+    let data = fs::read(path).with_context(|| format!("Failed to read font {}", path.display()))?;
+    FontArc::try_from_vec(data).with_context(|| format!("Invalid font file {}", path.display()))
+}
+
+/// 通过 fontconfig 查找字体族对应的文件。fc-match 总会返回一个替代字体，
+/// 因此需要确认返回的字体族与请求的一致
+#[cfg(target_os = "linux")]
+fn find_system_font(family: &str) -> Result<PathBuf> {
+    use std::process::Command;
+
+    let output = Command::new("fc-match")
+        .args(["--format", "%{family}\n%{file}", family])
+        .output()
+        .context("Failed to run fc-match")?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    let (families, file) = output.split_once('\n').unwrap_or_default();
+    // 一个字体可能有多个族名，用逗号分隔
+    if !families.split(',').any(|name| name.eq_ignore_ascii_case(family)) {
+        bail!("Font family not found: {}", family);
+    }
+    Ok(PathBuf::from(file))
+}
+
+/// 在系统字体目录中按文件名查找字体族，如 `Consolas` 对应 consola.ttf 或 Consolas-Regular.ttf
+#[cfg(not(target_os = "linux"))]
+fn find_system_font(family: &str) -> Result<PathBuf> {
+    let normalize = |s: &str| {
+        s.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase()
+    };
+    let wanted = normalize(family);
+    let matches = |stem: &str| {
+        let stem = normalize(stem);
+        stem == wanted || stem == format!("{wanted}regular")
+    };
+
+    for dir in system_font_dirs() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_font = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| ["ttf", "otf"].contains(&ext.to_ascii_lowercase().as_str()));
+            if is_font && path.file_stem().and_then(|s| s.to_str()).is_some_and(matches) {
+                return Ok(path);
+            }
+        }
+    }
+    bail!("Font family not found: {}", family)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn system_font_dirs() -> Vec<PathBuf> {
+    let mut font_dirs = Vec::new();
+    if cfg!(windows) {
+        let windir = std::env::var_os("WINDIR").unwrap_or_else(|| "C:\\Windows".into());
+        font_dirs.push(PathBuf::from(windir).join("Fonts"));
+        if let Some(local) = dirs::data_local_dir() {
+            font_dirs.push(local.join("Microsoft").join("Windows").join("Fonts"));
+        }
+    } else {
+        font_dirs.push(PathBuf::from("/System/Library/Fonts"));
+        font_dirs.push(PathBuf::from("/Library/Fonts"));
+        if let Some(home) = dirs::home_dir() {
+            font_dirs.push(home.join("Library").join("Fonts"));
+        }
+    }
+    font_dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use ab_glyph::Font;
+    use super::*;
+
+    #[test]
+    fn font_choice_round_trips() {
+        let cases = [
+            ("bundled:comic-mono", FontChoice::Bundled(BundledFont::ComicMono)),
+            ("bundled:dejavu-sans-mono", FontChoice::Bundled(BundledFont::DejaVuSansMono)),
+            ("file:/usr/share/fonts/Font Name.ttf", FontChoice::File(PathBuf::from("/usr/share/fonts/Font Name.ttf"))),
+            ("file:C:\\Fonts\\consola.ttf", FontChoice::File(PathBuf::from("C:\\Fonts\\consola.ttf"))),
+            ("family:DejaVu Sans", FontChoice::Family("DejaVu Sans".to_string())),
+        ];
+        for (input, choice) in cases {
+            assert_eq!(input.parse::<FontChoice>().unwrap(), choice, "{}", input);
+            assert_eq!(choice.to_string(), input);
+            assert_eq!(String::from(choice.clone()).parse::<FontChoice>().unwrap(), choice);
+        }
+        assert_eq!(
+            "family:  Noto Sans ".parse::<FontChoice>().unwrap(),
+            FontChoice::Family("Noto Sans".to_string())
+        );
+        for invalid in ["comic-mono", "bundled:comic", "file:", "family: ", "font:Arial", ""] {
+            assert!(invalid.parse::<FontChoice>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn bundled_fonts_load() {
+        for font in BundledFont::ALL {
+            assert_eq!(font.as_str().parse::<BundledFont>().unwrap(), font);
+            assert!(font.load().glyph_count() > 0);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_comic_mono() {
+        let garbage = env::temp_dir().join(format!("percentage-rust-font-{}.ttf", std::process::id()));
+        fs::write(&garbage, b"not a font").unwrap();
+        let comic_mono = BundledFont::ComicMono.load().glyph_count();
+        let choices = [
+            FontChoice::File(env::temp_dir().join("percentage-rust-missing-font.ttf")),
+            FontChoice::File(garbage.clone()),
+            FontChoice::Family("Percentage Rust Missing Font".to_string()),
+        ];
+        for choice in choices {
+            let (font, error) = choice.load_or_fallback();
+            assert!(error.is_some(), "{}", choice);
+            assert_eq!(font.glyph_count(), comic_mono, "{}", choice);
+        }
+        fs::remove_file(garbage).unwrap();
+
+        let (font, error) = FontChoice::Bundled(BundledFont::DejaVuSansMono).load_or_fallback();
+        assert!(error.is_none());
+        assert_ne!(font.glyph_count(), comic_mono);
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, OpenOptions},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+};
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+use crate::{battery_source::SourceKind, history::unix_now};
+
+/// 单块电池的健康信息，数据源不提供的字段为 None
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BatteryHealth {
+    pub name: String,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    /// 电池化学类型，如 Li-ion
+    pub technology: Option<String>,
+    /// 设计容量（Wh）
+    pub energy_full_design: Option<f32>,
+    /// 当前充满时的容量（Wh）
+    pub energy_full: Option<f32>,
+    pub cycle_count: Option<u32>,
+}
+
+impl BatteryHealth {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// 损耗百分比：相对设计容量减少的比例
+    pub fn wear(&self) -> Option<f32> {
+        let (full, design) = (self.energy_full?, self.energy_full_design?);
+        (design > 0.0).then(|| ((1.0 - full / design) * 100.0).max(0.0))
+    }
+}
+
+/// 导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Html,
+    Json,
+}
+
+impl ReportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Html => "html",
+            ReportFormat::Json => "json",
+        }
+    }
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "html" => Ok(ReportFormat::Html),
+            "json" => Ok(ReportFormat::Json),
+            _ => bail!("Unknown report format: {}", s),
+        }
+    }
+}
+
+/// 电池健康报告
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    /// 生成时间（Unix 时间戳，秒）
+    pub generated_at: u64,
+    pub source: String,
+    pub batteries: Vec<BatteryEntry>,
+}
+
+/// 报告中的一块电池，附带计算出的损耗
+#[derive(Debug, Clone, Serialize)]
+pub struct BatteryEntry {
+    #[serde(flatten)]
+    pub health: BatteryHealth,
+    /// 损耗百分比
+    pub wear_percent: Option<f32>,
+}
+
+impl HealthReport {
+    /// 同一秒内导出同名报告的上限
+    const MAX_SAME_NAME: u32 = 100;
+
+    /// 打开数据源读取所有电池的健康信息
+    pub fn collect(kind: &SourceKind) -> Result<Self> {
+        let batteries = kind
+            .open()?
+            .health()
+            .context("Failed to read battery health")?
+            .into_iter()
+            .map(|health| BatteryEntry {
+                wear_percent: health.wear(),
+                health,
+            })
+            .collect();
+        Ok(Self {
+            generated_at: unix_now(),
+            source: kind.to_string(),
+            batteries,
+        })
+    }
+
+    /// 导出到 dir 下以生成时间命名的文件，返回文件路径。同一秒内多次导出时
+    /// 文件名加上 `-2`、`-3` 等后缀，不会覆盖已有的报告
+    pub fn export(&self, dir: &Path, format: ReportFormat) -> Result<PathBuf> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let content = match format {
+            ReportFormat::Html => self.to_html(),
+            ReportFormat::Json => serde_json::to_string_pretty(self).context("Failed to serialize report")?,
+        };
+
+        let stem = format!("battery-report-{}", UtcTime::from_unix(self.generated_at).file_stamp());
+        for n in 1..=Self::MAX_SAME_NAME {
+            let name = match n {
+                1 => format!("{}.{}", stem, format.extension()),
+                n => format!("{}-{}.{}", stem, n, format.extension()),
+            };
+            let path = dir.join(name);
+            // create_new 保证不会覆盖同名文件，检查与创建之间也不会被抢先
+            let mut file = match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e).with_context(|| format!("Failed to create {}", path.display())),
+            };
+            file.write_all(content.as_bytes())
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            return Ok(path);
+        }
+        bail!("Too many reports named {} in {}", stem, dir.display())
+    }
+
+    /// 独立的 HTML 报告，不依赖外部资源
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"UTF-8\">\n\
+             <title>Battery report</title>\n<style>\n\
+             body {{ font: 14px system-ui, sans-serif; margin: 24px; }}\n\
+             table {{ border-collapse: collapse; margin-bottom: 24px; }}\n\
+             th, td {{ text-align: left; padding: 4px 16px 4px 0; border-bottom: 1px solid #ddd; }}\n\
+             th {{ color: #666; font-weight: normal; }}\n\
+             </style>\n</head>\n<body>\n<h1>Battery report</h1>\n\
+             <p>Generated {} from source <code>{}</code></p>\n",
+            format_utc(self.generated_at),
+            escape(&self.source)
+        );
+        if self.batteries.is_empty() {
+            html.push_str("<p>No battery information is available from this source.</p>\n");
+        }
+        for entry in &self.batteries {
+            let health = &entry.health;
+            let _ = writeln!(html, "<h2>{}</h2>\n<table>", escape(&health.name));
+            let rows = [
+                ("Manufacturer", health.vendor.clone()),
+                ("Model", health.model.clone()),
+                ("Serial number", health.serial_number.clone()),
+                ("Chemistry", health.technology.clone()),
+                ("Design capacity", health.energy_full_design.map(|e| format!("{:.1} Wh", e))),
+                ("Full charge capacity", health.energy_full.map(|e| format!("{:.1} Wh", e))),
+                ("Wear level", entry.wear_percent.map(|w| format!("{:.1}%", w))),
+                ("Cycle count", health.cycle_count.map(|c| c.to_string())),
+            ];
+            for (label, value) in rows {
+                let value = value.map(|v| escape(&v)).unwrap_or_else(|| "—".to_string());
+                let _ = writeln!(html, "<tr><th>{}</th><td>{}</td></tr>", label, value);
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+/// 转义 HTML 特殊字符
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// 将 Unix 时间戳格式化为 `2024-01-31 08:05 UTC`
+fn format_utc(timestamp: u64) -> String {
+    let t = UtcTime::from_unix(timestamp);
+    format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", t.year, t.month, t.day, t.hour, t.minute)
+}
+
+/// Unix 时间戳对应的 UTC 日期与时间
+struct UtcTime {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: u64,
+    minute: u64,
+    second: u64,
+}
+
+impl UtcTime {
+    fn from_unix(timestamp: u64) -> Self {
+        let days = (timestamp / 86400) as i64;
+
+        // 由天数推算公历日期（Howard Hinnant 的 civil_from_days 算法）
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Self {
+            year,
+            month,
+            day,
+            hour: timestamp % 86400 / 3600,
+            minute: timestamp % 3600 / 60,
+            second: timestamp % 60,
+        }
+    }
+
+    /// 用于文件名，如 `2024-01-31-08-05-09`
+    fn file_stamp(&self) -> String {
+        format!(
+            "{:04}-{:02}-{:02}-{:02}-{:02}-{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use super::*;
+
+    fn health(design: Option<f32>, full: Option<f32>) -> BatteryHealth {
+        BatteryHealth {
+            energy_full_design: design,
+            energy_full: full,
+            ..BatteryHealth::new("BAT0")
+        }
+    }
+
+    #[test]
+    fn wear_compares_full_to_design_capacity() {
+        let cases = [
+            (Some(50.0), Some(40.0), Some(20.0)),
+            (Some(50.0), Some(50.0), Some(0.0)),
+            // 新电池的满充容量可能略高于设计容量，不报告负的损耗
+            (Some(50.0), Some(52.0), Some(0.0)),
+            (Some(0.0), Some(40.0), None),
+            (None, Some(40.0), None),
+            (Some(50.0), None, None),
+        ];
+        for (design, full, expected) in cases {
+            let wear = health(design, full).wear();
+            match (wear, expected) {
+                (Some(wear), Some(expected)) => assert!((wear - expected).abs() < 1e-3, "{:?} {:?}", design, full),
+                _ => assert_eq!(wear, expected, "{:?} {:?}", design, full),
+            }
+        }
+    }
+
+    #[test]
+    fn formats_utc_dates() {
+        let cases = [
+            (0, "1970-01-01 00:00 UTC", "1970-01-01-00-00-00"),
+            (951_782_400, "2000-02-29 00:00 UTC", "2000-02-29-00-00-00"),
+            (1_706_688_309, "2024-01-31 08:05 UTC", "2024-01-31-08-05-09"),
+            (4_107_542_399, "2100-02-28 23:59 UTC", "2100-02-28-23-59-59"),
+        ];
+        for (timestamp, display, stamp) in cases {
+            assert_eq!(format_utc(timestamp), display);
+            assert_eq!(UtcTime::from_unix(timestamp).file_stamp(), stamp);
+        }
+    }
+
+    #[test]
+    fn export_does_not_overwrite_reports() {
+        let dir = env::temp_dir().join(format!("percentage-rust-health-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let report = HealthReport {
+            generated_at: 1_706_688_309,
+            source: "sysfs:<test>".to_string(),
+            batteries: vec![BatteryEntry {
+                wear_percent: Some(20.0),
+                health: health(Some(50.0), Some(40.0)),
+            }],
+        };
+
+        let paths: Vec<PathBuf> = (0..3)
+            .map(|_| report.export(&dir, ReportFormat::Html).unwrap())
+            .chain([report.export(&dir, ReportFormat::Json).unwrap()])
+            .collect();
+        let names: Vec<&str> = paths.iter().map(|p| p.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(
+            names,
+            [
+                "battery-report-2024-01-31-08-05-09.html",
+                "battery-report-2024-01-31-08-05-09-2.html",
+                "battery-report-2024-01-31-08-05-09-3.html",
+                "battery-report-2024-01-31-08-05-09.json",
+            ]
+        );
+
+        let html = fs::read_to_string(&paths[0]).unwrap();
+        assert!(html.contains("<code>sysfs:&lt;test&gt;</code>"));
+        assert!(html.contains("<tr><th>Wear level</th><td>20.0%</td></tr>"));
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&paths[3]).unwrap()).unwrap();
+        assert_eq!(json["batteries"][0]["name"], "BAT0");
+        assert_eq!(json["batteries"][0]["wear_percent"], 20.0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
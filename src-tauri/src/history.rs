@@ -0,0 +1,388 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    battery_source::{BatteryReading, ChargeState},
+    settings::{duration_secs, zero_as_none},
+};
+
+/// 电量历史记录的设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    pub enabled: bool,
+    /// 电量和状态不变时每隔这么长时间记录一次，功率的微小波动不会单独写入
+    #[serde(rename = "min_interval_secs", with = "duration_secs")]
+    pub min_interval: Duration,
+    /// 记录保留的天数，0 表示永久保留
+    #[serde(with = "zero_as_none")]
+    pub retention_days: Option<u32>,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_interval: Duration::from_secs(60),
+            retention_days: Some(30),
+        }
+    }
+}
+
+impl HistoryConfig {
+    /// 电量不变时两条记录最多相隔两个间隔，须小于图表中视为中断的间隔（15 分钟）
+    const MAX_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.min_interval >= Duration::from_secs(1) && self.min_interval <= Self::MAX_INTERVAL,
+            "history.min_interval_secs must be between 1 and {}",
+            Self::MAX_INTERVAL.as_secs()
+        );
+        Ok(())
+    }
+
+    fn retention(&self) -> Option<Duration> {
+        self.retention_days.map(|days| Duration::from_secs(days as u64 * 24 * 3600))
+    }
+}
+
+/// 一条历史记录，对应某一时刻一块电池的读数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    /// Unix 时间戳（秒）
+    pub timestamp: u64,
+    pub battery: String,
+    pub percentage: u32,
+    pub state: ChargeState,
+    /// 充放电功率（W）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub energy_rate: Option<f32>,
+    /// 电压（V）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voltage: Option<f32>,
+}
+
+impl HistoryRecord {
+    fn new(timestamp: u64, reading: &BatteryReading) -> Self {
+        Self {
+            timestamp,
+            battery: reading.name.clone(),
+            percentage: reading.percentage,
+            state: reading.state,
+            energy_rate: reading.energy_rate,
+            voltage: reading.voltage,
+        }
+    }
+}
+
+/// 写入状态：打开的文件与每块电池最近一次写入的记录
+#[derive(Default)]
+struct Writer {
+    file: Option<BufWriter<File>>,
+    last_written: HashMap<String, HistoryRecord>,
+    last_pruned: Option<u64>,
+}
+
+/// 电量历史，以每行一条 JSON 记录的形式追加写入文件，重启后继续追加
+pub struct HistoryStore {
+    path: PathBuf,
+    writer: Mutex<Writer>,
+    /// 找不到数据目录时为 false，不读写文件
+    available: bool,
+}
+
+impl HistoryStore {
+    /// 清理过期记录的间隔
+    const PRUNE_INTERVAL: u64 = 24 * 3600;
+
+    /// 平台数据目录下的 percentage-rust/history.jsonl
+    pub fn default_path() -> Result<PathBuf> {
+        let dir = dirs::data_dir().context("Failed to locate data directory")?;
+        Ok(dir.join("percentage-rust").join("history.jsonl"))
+    }
+
+    /// 文件在第一次写入时才创建
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            writer: Mutex::new(Writer::default()),
+            available: true,
+        }
+    }
+
+    /// 不记录也不读取任何内容的历史，在无法确定文件位置时使用
+    pub fn unavailable() -> Self {
+        Self {
+            available: false,
+            ..Self::new(PathBuf::new())
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 记录一组读数。电量或状态变化时立即写入，否则每块电池至少间隔 min_interval 才写一次
+    pub fn record(&self, config: &HistoryConfig, readings: &[BatteryReading]) -> Result<()> {
+        if !config.enabled || !self.available {
+            return Ok(());
+        }
+        let mut writer = self.writer.lock().unwrap();
+        // 取得锁之后再取时间，多个线程同时写入时记录仍按时间顺序排列
+        let now = unix_now();
+
+        if let Some(retention) = config.retention() {
+            if writer.last_pruned.is_none_or(|t| now >= t + Self::PRUNE_INTERVAL) {
+                writer.last_pruned = Some(now);
+                // 重写文件前先关闭当前的写入句柄
+                writer.file = None;
+                self.prune(now.saturating_sub(retention.as_secs()))?;
+            }
+        }
+
+        for reading in readings {
+            let record = HistoryRecord::new(now, reading);
+            let due = match writer.last_written.get(&record.battery) {
+                Some(last) => {
+                    last.percentage != record.percentage
+                        || last.state != record.state
+                        || now >= last.timestamp + config.min_interval.as_secs()
+                }
+                None => true,
+            };
+            if !due {
+                continue;
+            }
+
+            let file = match &mut writer.file {
+                Some(file) => file,
+                file => file.insert(self.open_for_append()?),
+            };
+            let line = serde_json::to_string(&record).context("Failed to serialize history record")?;
+            writeln!(file, "{}", line)
+                .and_then(|_| file.flush())
+                .with_context(|| format!("Failed to write {}", self.path.display()))?;
+            writer.last_written.insert(record.battery.clone(), record);
+        }
+        Ok(())
+    }
+
+    /// 读取 since（Unix 时间戳）之后的记录，文件不存在时返回空列表。无法解析的行会被跳过
+    pub fn load(&self, since: u64) -> Result<Vec<HistoryRecord>> {
+        if !self.available {
+            return Ok(Vec::new());
+        }
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to open {}", self.path.display())),
+        };
+        let mut records = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.with_context(|| format!("Failed to read {}", self.path.display()))?;
+            // 程序意外退出时最后一行可能不完整
+            if let Ok(record) = serde_json::from_str::<HistoryRecord>(&line) {
+                if record.timestamp >= since {
+                    records.push(record);
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    fn open_for_append(&self) -> Result<BufWriter<File>> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+
+        // 上次意外退出时最后一行可能没有写完，从新的一行开始追加
+        let mut last = [0u8];
+        if file.seek(SeekFrom::End(-1)).is_ok() && file.read_exact(&mut last).is_ok() && last[0] != b'\n' {
+            writeln!(file).with_context(|| format!("Failed to write {}", self.path.display()))?;
+        }
+        Ok(BufWriter::new(file))
+    }
+
+    /// 删除 before（Unix 时间戳）之前的记录：保留的记录先写入临时文件，再替换原文件
+    fn prune(&self, before: u64) -> Result<()> {
+        let records = self.load(before)?;
+        if records.is_empty() && !self.path.exists() {
+            return Ok(());
+        }
+
+        let temp = self.path.with_extension("jsonl.tmp");
+        {
+            let file = File::create(&temp).with_context(|| format!("Failed to create {}", temp.display()))?;
+            let mut file = BufWriter::new(file);
+            for record in &records {
+                let line = serde_json::to_string(record).context("Failed to serialize history record")?;
+                writeln!(file, "{}", line).with_context(|| format!("Failed to write {}", temp.display()))?;
+            }
+            file.flush().with_context(|| format!("Failed to write {}", temp.display()))?;
+        }
+        fs::rename(&temp, &self.path)
+            .with_context(|| format!("Failed to replace {}", self.path.display()))
+    }
+}
+
+/// 当前的 Unix 时间戳（秒）
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("percentage-rust-history-{}-{}.jsonl", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn line(timestamp: u64, battery: &str, percentage: u32) -> String {
+        format!(
+            r#"{{"timestamp":{},"battery":"{}","percentage":{},"state":"discharging"}}"#,
+            timestamp, battery, percentage
+        )
+    }
+
+    fn timestamps(store: &HistoryStore) -> Vec<u64> {
+        store.load(0).unwrap().iter().map(|record| record.timestamp).collect()
+    }
+
+    #[test]
+    fn records_changes_and_loads_since() {
+        let path = temp_path("record");
+        let store = HistoryStore::new(path.clone());
+        let config = HistoryConfig::default();
+        assert!(store.load(0).unwrap().is_empty());
+
+        let readings = [
+            BatteryReading::new("BAT0", 80, ChargeState::Discharging),
+            BatteryReading::new("BAT1", 60, ChargeState::Discharging),
+        ];
+        store.record(&config, &readings).unwrap();
+        // 电量与状态不变，未到 min_interval 不再写入
+        store.record(&config, &readings).unwrap();
+        store
+            .record(&config, &[BatteryReading::new("BAT0", 79, ChargeState::Discharging)])
+            .unwrap();
+
+        let records = store.load(0).unwrap();
+        let summary: Vec<(&str, u32)> = records.iter().map(|r| (r.battery.as_str(), r.percentage)).collect();
+        assert_eq!(summary, [("BAT0", 80), ("BAT1", 60), ("BAT0", 79)]);
+        assert!(store.load(unix_now() + 1).unwrap().is_empty());
+
+        // 重新打开后继续追加
+        let reopened = HistoryStore::new(path.clone());
+        reopened.record(&config, &readings[..1]).unwrap();
+        assert_eq!(reopened.load(0).unwrap().len(), 4);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn load_filters_by_timestamp() {
+        let path = temp_path("since");
+        let lines = [line(100, "BAT0", 50), line(200, "BAT0", 49), line(300, "BAT0", 48)];
+        fs::write(&path, lines.join("\n") + "\n").unwrap();
+        let store = HistoryStore::new(path.clone());
+        let since: Vec<u64> = store.load(200).unwrap().iter().map(|r| r.timestamp).collect();
+        assert_eq!(since, [200, 300]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn skips_corrupt_and_truncated_lines() {
+        let path = temp_path("corrupt");
+        let truncated = &line(300, "BAT0", 48)[..30];
+        let lines = [line(100, "BAT0", 50), "not json".to_string(), line(200, "BAT0", 49), truncated.to_string()];
+        fs::write(&path, lines.join("\n")).unwrap();
+        let store = HistoryStore::new(path.clone());
+        assert_eq!(timestamps(&store), [100, 200]);
+
+        // 追加的记录另起一行，不会与不完整的最后一行拼在一起
+        let config = HistoryConfig { retention_days: None, ..HistoryConfig::default() };
+        store
+            .record(&config, &[BatteryReading::new("BAT0", 47, ChargeState::Discharging)])
+            .unwrap();
+        let records = store.load(0).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].percentage, 47);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn prunes_records_past_retention() {
+        let now = unix_now();
+        let day = 24 * 3600;
+        let lines = [line(now - 40 * day, "BAT0", 90), line(now - 10 * day, "BAT0", 80), line(now - day, "BAT0", 70)];
+        let content = lines.join("\n") + "\n";
+        let reading = [BatteryReading::new("BAT0", 60, ChargeState::Discharging)];
+
+        let path = temp_path("prune");
+        fs::write(&path, &content).unwrap();
+        let store = HistoryStore::new(path.clone());
+        let config = HistoryConfig { retention_days: Some(30), ..HistoryConfig::default() };
+        store.record(&config, &reading).unwrap();
+        let kept: Vec<u32> = store.load(0).unwrap().iter().map(|r| r.percentage).collect();
+        assert_eq!(kept, [80, 70, 60]);
+        assert!(!path.with_extension("jsonl.tmp").exists());
+
+        let config = HistoryConfig { retention_days: Some(5), ..HistoryConfig::default() };
+        let store = HistoryStore::new(path.clone());
+        store.record(&config, &reading).unwrap();
+        let kept: Vec<u32> = store.load(0).unwrap().iter().map(|r| r.percentage).collect();
+        assert_eq!(kept, [70, 60, 60]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn zero_retention_keeps_everything() {
+        let now = unix_now();
+        let path = temp_path("keep");
+        fs::write(&path, line(now - 400 * 24 * 3600, "BAT0", 90) + "\n").unwrap();
+        let store = HistoryStore::new(path.clone());
+        let config: HistoryConfig = toml::from_str("retention_days = 0").unwrap();
+        assert_eq!(config.retention_days, None);
+        store
+            .record(&config, &[BatteryReading::new("BAT0", 60, ChargeState::Discharging)])
+            .unwrap();
+        assert_eq!(store.load(0).unwrap().len(), 2);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn disabled_and_unavailable_stores_write_nothing() {
+        let path = temp_path("disabled");
+        let reading = [BatteryReading::new("BAT0", 60, ChargeState::Discharging)];
+        let store = HistoryStore::new(path.clone());
+        store
+            .record(&HistoryConfig { enabled: false, ..HistoryConfig::default() }, &reading)
+            .unwrap();
+        assert!(!path.exists());
+
+        let store = HistoryStore::unavailable();
+        store.record(&HistoryConfig::default(), &reading).unwrap();
+        assert!(store.load(0).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,430 @@
+use std::{net::Ipv4Addr, sync::Arc, time::Duration};
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    sync::watch,
+    task::JoinSet,
+    time::{sleep, timeout},
+};
+
+use crate::{
+    battery_source::SourceUpdate,
+    history::{unix_now, HistoryStore},
+    settings::Settings,
+    status::StatusReport,
+};
+
+/// 本地 HTTP 服务的设置，默认关闭
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    pub enabled: bool,
+    /// 监听端口，只绑定 127.0.0.1
+    pub port: u16,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 7331,
+        }
+    }
+}
+
+impl HttpConfig {
+    pub fn validate(&self) -> Result<()> {
+        ensure!(self.port != 0, "http.port must not be 0");
+        Ok(())
+    }
+}
+
+/// 各个连接共享的数据
+struct ServerState {
+    port: u16,
+    updates: watch::Receiver<Option<SourceUpdate>>,
+    settings: watch::Receiver<Settings>,
+    history: Arc<HistoryStore>,
+}
+
+impl ServerState {
+    fn status(&self) -> StatusReport {
+        let aggregation = self.settings.borrow().aggregation;
+        StatusReport::from_update(self.updates.borrow().as_ref(), aggregation)
+    }
+}
+
+/// 按设置启停本地 HTTP 服务，端口变化时重新监听。updates 与托盘订阅的是同一个监视器，
+/// 不会额外读取电池。监听失败时调用 on_error
+pub async fn run_http_server(
+    mut settings_rx: watch::Receiver<Settings>,
+    updates: watch::Receiver<Option<SourceUpdate>>,
+    history: Arc<HistoryStore>,
+    on_error: impl Fn(anyhow::Error) + Send + Sync + 'static,
+) {
+    let on_error = Arc::new(on_error);
+    let mut config = settings_rx.borrow_and_update().http.clone();
+
+    loop {
+        let server = config.enabled.then(|| {
+            let state = Arc::new(ServerState {
+                port: config.port,
+                updates: updates.clone(),
+                settings: settings_rx.clone(),
+                history: history.clone(),
+            });
+            let on_error = on_error.clone();
+            tokio::spawn(async move {
+                if let Err(e) = serve(state).await {
+                    on_error(e);
+                }
+            })
+        });
+
+        // 只有 http 部分的设置变化时才重启服务
+        loop {
+            if settings_rx.changed().await.is_err() {
+                return;
+            }
+            let new_config = settings_rx.borrow_and_update().http.clone();
+            if new_config != config {
+                config = new_config;
+                break;
+            }
+        }
+        // 停止监听，同时结束所有连接（包括 /events 的长连接）
+        if let Some(server) = server {
+            server.abort();
+        }
+    }
+}
+
+/// 监听 127.0.0.1 并为每个连接启动一个任务，连接任务随本任务一起结束
+async fn serve(state: Arc<ServerState>) -> Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, state.port))
+        .await
+        .with_context(|| format!("Failed to listen on 127.0.0.1:{}", state.port))?;
+    let mut connections = JoinSet::new();
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    let state = state.clone();
+                    connections.spawn(async move {
+                        // 客户端断开等错误只影响这一个连接
+                        if let Err(e) = handle_connection(stream, &state).await {
+                            eprintln!("HTTP request failed: {:#}", e);
+                        }
+                    });
+                }
+                Err(e) => eprintln!("Failed to accept HTTP connection: {}", e),
+            },
+            // 回收已结束的连接任务
+            Some(_) = connections.join_next() => {}
+        }
+    }
+}
+
+/// 一个请求的方法、路径和查询参数
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+}
+
+impl Request {
+    /// 请求头的最大长度
+    const MAX_HEAD: u64 = 8 * 1024;
+    const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// 读取请求行和请求头，拒绝 Host 不是本机的请求，防止网页通过 DNS 重绑定读取数据
+    async fn read(stream: &mut (impl AsyncRead + Unpin), port: u16) -> Result<Self> {
+        let mut reader = BufReader::new((&mut *stream).take(Self::MAX_HEAD));
+        let mut request_line = String::new();
+        let mut host = None;
+        timeout(Self::READ_TIMEOUT, async {
+            reader.read_line(&mut request_line).await?;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).await? == 0 {
+                    // 读到上限时被截断，否则是客户端提前关闭了连接
+                    ensure!(reader.get_ref().limit() > 0, "Request header too large");
+                    bail!("Incomplete request header");
+                }
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.trim().eq_ignore_ascii_case("host") {
+                        host = Some(value.trim().to_string());
+                    }
+                }
+            }
+            anyhow::Ok(())
+        })
+        .await
+        .context("Timed out reading request")??;
+
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            bail!("Malformed request line: {:?}", request_line.trim());
+        };
+        let allowed_hosts = [format!("127.0.0.1:{}", port), format!("localhost:{}", port)];
+        if !host.is_some_and(|host| allowed_hosts.contains(&host)) {
+            bail!("Rejected request with unexpected Host header");
+        }
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let query = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (key.to_string(), value.to_string())
+            })
+            .collect();
+        Ok(Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            query,
+        })
+    }
+
+    fn param(&self, key: &str) -> Option<&str> {
+        self.query.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+}
+
+async fn handle_connection(mut stream: impl AsyncRead + AsyncWrite + Unpin, state: &ServerState) -> Result<()> {
+    let request = match Request::read(&mut stream, state.port).await {
+        Ok(request) => request,
+        Err(e) => {
+            respond(&mut stream, "400 Bad Request", "text/plain", format!("{:#}\n", e).as_bytes()).await?;
+            return Ok(());
+        }
+    };
+    if request.method != "GET" {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"Only GET is supported\n").await;
+    }
+
+    match request.path.as_str() {
+        "/status" => {
+            let body = serde_json::to_vec(&state.status()).context("Failed to serialize status")?;
+            respond(&mut stream, "200 OK", "application/json", &body).await
+        }
+        "/history" => {
+            let hours = match request.param("hours").map(str::parse::<u64>) {
+                None => 24,
+                Some(Ok(hours)) => hours,
+                Some(Err(_)) => {
+                    return respond(&mut stream, "400 Bad Request", "text/plain", b"Invalid hours\n").await;
+                }
+            };
+            let since = unix_now().saturating_sub(hours.saturating_mul(3600));
+            // 读取整个历史文件可能较慢，不占用异步运行时的工作线程
+            let history = state.history.clone();
+            let records = tokio::task::spawn_blocking(move || history.load(since))
+                .await
+                .context("History task failed")?;
+            match records {
+                Ok(records) => {
+                    let body = serde_json::to_vec(&records).context("Failed to serialize history")?;
+                    respond(&mut stream, "200 OK", "application/json", &body).await
+                }
+                Err(e) => {
+                    let message = format!("{:#}\n", e);
+                    respond(&mut stream, "500 Internal Server Error", "text/plain", message.as_bytes()).await
+                }
+            }
+        }
+        "/events" => stream_events(&mut stream, state).await,
+        _ => respond(&mut stream, "404 Not Found", "text/plain", b"Not found\n").await,
+    }
+}
+
+async fn respond(stream: &mut (impl AsyncWrite + Unpin), status: &str, content_type: &str, body: &[u8]) -> Result<()> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Server-Sent Events：连接后立即发送当前状态，之后每次变化发送一条 status 事件
+async fn stream_events(stream: &mut (impl AsyncWrite + Unpin), state: &ServerState) -> Result<()> {
+    /// 没有变化时定期发送注释行，避免连接被代理或客户端判定为超时
+    const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+    stream
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-store\r\nConnection: keep-alive\r\n\r\n")
+        .await?;
+    let mut updates = state.updates.clone();
+
+    loop {
+        updates.mark_unchanged();
+        let status = serde_json::to_string(&state.status()).context("Failed to serialize status")?;
+        stream.write_all(format!("event: status\ndata: {}\n\n", status).as_bytes()).await?;
+        stream.flush().await?;
+
+        loop {
+            tokio::select! {
+                changed = updates.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    break;
+                }
+                _ = sleep(KEEP_ALIVE) => {
+                    stream.write_all(b": keep-alive\n\n").await?;
+                    stream.flush().await?;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, future::Future, path::PathBuf};
+    use super::*;
+
+    const PORT: u16 = 7331;
+
+    fn run<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn state(history: PathBuf) -> ServerState {
+        ServerState {
+            port: PORT,
+            updates: watch::channel(None).1,
+            settings: watch::channel(Settings::default()).1,
+            history: Arc::new(HistoryStore::new(history)),
+        }
+    }
+
+    /// 把原始请求交给 handle_connection，返回完整的响应
+    fn exchange(state: &ServerState, request: &str) -> String {
+        run(async {
+            let (mut client, server) = tokio::io::duplex(64 * 1024);
+            client.write_all(request.as_bytes()).await.unwrap();
+            // 关闭写入方向，不完整的请求会读到结尾而不是等到超时
+            client.shutdown().await.unwrap();
+            handle_connection(server, state).await.unwrap();
+            let mut response = String::new();
+            client.read_to_string(&mut response).await.unwrap();
+            response
+        })
+    }
+
+    fn get(state: &ServerState, target: &str) -> String {
+        exchange(state, &format!("GET {} HTTP/1.1\r\nHost: 127.0.0.1:{}\r\n\r\n", target, PORT))
+    }
+
+    fn status_line(response: &str) -> &str {
+        response.lines().next().unwrap_or_default()
+    }
+
+    fn body(response: &str) -> &str {
+        response.split_once("\r\n\r\n").map(|(_, body)| body).unwrap_or_default()
+    }
+
+    #[test]
+    fn parses_request_line_and_query() {
+        let mut raw = "GET /history?hours=6&x HTTP/1.1\r\nhost: localhost:7331\r\nAccept: */*\r\n\r\n".as_bytes();
+        let request = run(Request::read(&mut raw, PORT)).unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/history");
+        assert_eq!(request.param("hours"), Some("6"));
+        assert_eq!(request.param("x"), Some(""));
+        assert_eq!(request.param("y"), None);
+    }
+
+    #[test]
+    fn rejects_unexpected_hosts() {
+        let state = state(PathBuf::new());
+        let hosts = ["Host: evil.example:7331\r\n", "Host: 127.0.0.1:8080\r\n", "Host: 127.0.0.1\r\n", ""];
+        for host in hosts {
+            let response = exchange(&state, &format!("GET /status HTTP/1.1\r\n{}\r\n", host));
+            assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request", "{:?}", host);
+            assert!(body(&response).contains("Host"), "{}", response);
+        }
+        let response = exchange(&state, "GET /status HTTP/1.1\r\nHost: localhost:7331\r\n\r\n");
+        assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+    }
+
+    #[test]
+    fn rejects_oversized_and_incomplete_headers() {
+        let state = state(PathBuf::new());
+        let padding = "x".repeat(Request::MAX_HEAD as usize);
+        let request = format!("GET /status HTTP/1.1\r\nHost: 127.0.0.1:{}\r\nX-Padding: {}\r\n\r\n", PORT, padding);
+        let response = exchange(&state, &request);
+        assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+        assert!(body(&response).contains("too large"), "{}", response);
+
+        let response = exchange(&state, "GET /status HTTP/1.1\r\nHost: 127.0.0.1:7331\r\n");
+        assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+        let response = exchange(&state, "\r\n\r\n");
+        assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request");
+    }
+
+    #[test]
+    fn routes_requests() {
+        let state = state(PathBuf::new());
+        let response = get(&state, "/status");
+        assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+        assert!(response.contains("Content-Type: application/json\r\n"));
+        let status: serde_json::Value = serde_json::from_str(body(&response)).unwrap();
+        assert_eq!(status["batteries"], serde_json::json!([]));
+
+        assert_eq!(status_line(&get(&state, "/")), "HTTP/1.1 404 Not Found");
+        assert_eq!(status_line(&get(&state, "/statusx")), "HTTP/1.1 404 Not Found");
+        let response = exchange(&state, "POST /status HTTP/1.1\r\nHost: 127.0.0.1:7331\r\n\r\n");
+        assert_eq!(status_line(&response), "HTTP/1.1 405 Method Not Allowed");
+    }
+
+    #[test]
+    fn history_filters_by_hours() {
+        let path = env::temp_dir().join(format!("percentage-rust-http-history-{}.jsonl", std::process::id()));
+        let now = unix_now();
+        let record = |age: u64, percentage: u32| {
+            format!(
+                r#"{{"timestamp":{},"battery":"BAT0","percentage":{},"state":"discharging"}}"#,
+                now - age,
+                percentage
+            )
+        };
+        fs::write(&path, [record(48 * 3600, 90), record(3600, 80)].join("\n") + "\n").unwrap();
+        let state = state(path.clone());
+
+        let percentages = |target: &str| {
+            let response = get(&state, target);
+            assert_eq!(status_line(&response), "HTTP/1.1 200 OK", "{}", target);
+            let records: Vec<serde_json::Value> = serde_json::from_str(body(&response)).unwrap();
+            records.iter().map(|r| r["percentage"].as_u64().unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(percentages("/history"), [80]);
+        assert_eq!(percentages("/history?hours=72"), [90, 80]);
+        assert!(percentages("/history?hours=0").is_empty());
+        assert_eq!(percentages("/history?hours=99999999999999999"), [90, 80]);
+
+        for hours in ["abc", "-1", "1.5", ""] {
+            let response = get(&state, &format!("/history?hours={}", hours));
+            assert_eq!(status_line(&response), "HTTP/1.1 400 Bad Request", "{:?}", hours);
+            assert_eq!(body(&response), "Invalid hours\n");
+        }
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,409 @@
+use std::{fmt, str::FromStr};
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    battery_source::ChargeState, fonts::FontChoice, settings::zero_as_none, template::Template,
+};
+
+/// RGBA 颜色，配置文件中写作 `#rrggbb` 或 `#rrggbbaa`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color(pub [u8; 4]);
+
+impl Color {
+    pub const BLACK: Color = Color([0, 0, 0, 255]);
+    pub const WHITE: Color = Color([255, 255, 255, 255]);
+    pub const TRANSPARENT: Color = Color([0, 0, 0, 0]);
+
+    /// 在此颜色上清晰可见的黑色或白色
+    pub fn contrasting(&self) -> Color {
+        let [r, g, b, _] = self.0;
+        let luminance = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+        if luminance > 140.0 { Color::BLACK } else { Color::WHITE }
+    }
+}
+
+impl FromStr for Color {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        // from_str_radix 会接受前导的 `+`，需先确认全是十六进制数字
+        ensure!(hex.chars().all(|c| c.is_ascii_hexdigit()), "Invalid color: {}", s);
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                .with_context(|| format!("Invalid color: {}", s))
+        };
+        match hex.len() {
+            6 => Ok(Color([channel(0)?, channel(2)?, channel(4)?, 255])),
+            8 => Ok(Color([channel(0)?, channel(2)?, channel(4)?, channel(6)?])),
+            _ => bail!("Invalid color: {} (expected #rrggbb or #rrggbbaa)", s),
+        }
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b, a] = self.0;
+        if a == 255 {
+            write!(f, "#{:02x}{:02x}{:02x}", r, g, b)
+        } else {
+            write!(f, "#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+        }
+    }
+}
+
+/// 一套图标配色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+    /// 文字颜色
+    pub foreground: Color,
+    /// 背景色，不设置时背景透明
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<Color>,
+    /// 文字描边颜色，不设置时不描边
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outline: Option<Color>,
+}
+
+impl Theme {
+    /// 适用于浅色托盘背景
+    pub const LIGHT: Theme = Theme {
+        foreground: Color::BLACK,
+        background: None,
+        outline: None,
+    };
+    /// 适用于深色托盘背景
+    pub const DARK: Theme = Theme {
+        foreground: Color::WHITE,
+        background: None,
+        outline: None,
+    };
+}
+
+/// 按电量着色的规则：高于 high 为 high_color，低于等于 low 为 low_color，其余为 medium_color
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorRamp {
+    /// 关闭时使用主题的文字颜色
+    pub enabled: bool,
+    pub high: u32,
+    pub low: u32,
+    pub high_color: Color,
+    pub medium_color: Color,
+    pub low_color: Color,
+    /// 充电或接通电源暂停充电时的颜色，不受电量影响
+    pub charging_color: Color,
+}
+
+impl Default for ColorRamp {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            high: 50,
+            low: 20,
+            high_color: Color([0x2e, 0x9d, 0x3a, 255]),
+            medium_color: Color([0xf0, 0x8c, 0x00, 255]),
+            low_color: Color([0xd9, 0x30, 0x25, 255]),
+            charging_color: Color([0x1e, 0x88, 0xe5, 255]),
+        }
+    }
+}
+
+impl ColorRamp {
+    /// 电量和状态对应的文字颜色，未启用时返回 None
+    pub fn color_for(&self, percentage: u32, state: ChargeState) -> Option<Color> {
+        if !self.enabled {
+            return None;
+        }
+        Some(if matches!(state, ChargeState::Charging | ChargeState::NotCharging) {
+            self.charging_color
+        } else if percentage > self.high {
+            self.high_color
+        } else if percentage > self.low {
+            self.medium_color
+        } else {
+            self.low_color
+        })
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ensure!(self.high <= 100, "icon.colors.high must be between 0 and 100, got {}", self.high);
+        ensure!(
+            self.low < self.high,
+            "icon.colors.low ({}) must be lower than icon.colors.high ({})",
+            self.low,
+            self.high
+        );
+        Ok(())
+    }
+}
+
+/// 托盘背景的深浅
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Appearance {
+    #[default]
+    Light,
+    Dark,
+}
+
+/// 图标的绘制方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IconMode {
+    /// 只显示电量数字
+    #[default]
+    Text,
+    /// 电池外形与电量条
+    Glyph,
+    /// 显示当前充放电功率
+    Power,
+}
+
+impl IconMode {
+    pub const ALL: [IconMode; 3] = [IconMode::Text, IconMode::Glyph, IconMode::Power];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IconMode::Text => "text",
+            IconMode::Glyph => "glyph",
+            IconMode::Power => "power",
+        }
+    }
+
+    /// 托盘菜单中显示的名称
+    pub fn label(&self) -> &'static str {
+        match self {
+            IconMode::Text => "Percentage text",
+            IconMode::Glyph => "Battery glyph",
+            IconMode::Power => "Power draw",
+        }
+    }
+}
+
+impl FromStr for IconMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match IconMode::ALL.into_iter().find(|mode| mode.as_str() == s) {
+            Some(mode) => Ok(mode),
+            None => bail!("Unknown icon mode: {}", s),
+        }
+    }
+}
+
+/// 选择配色的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeMode {
+    /// 跟随系统的深浅色设置
+    #[default]
+    Auto,
+    Light,
+    Dark,
+}
+
+/// 图标的配色与文字格式
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IconStyle {
+    pub mode: IconMode,
+    /// 文字字体，如 `bundled:comic-mono`、`family:DejaVu Sans`、`file:/path/to/font.ttf`
+    pub font: FontChoice,
+    /// 电池外形模式下是否在电池上叠加电量数字
+    pub glyph_number: bool,
+    pub theme: ThemeMode,
+    /// 图标文字模板，如 `{pct}{charging:*}`
+    pub text: Template,
+    /// 功率模式下的图标文字模板
+    pub power_text: Template,
+    /// 充满，或接通电源且电量高于 full_threshold 时显示的文字
+    pub full_text: String,
+    pub full_threshold: u32,
+    /// 浅色背景下的配色
+    pub light: Theme,
+    /// 深色背景下的配色
+    pub dark: Theme,
+    /// 按电量着色，启用时覆盖配色中的文字颜色
+    pub colors: ColorRamp,
+    /// 图标边长（像素），0 表示根据屏幕缩放自动选择
+    #[serde(with = "zero_as_none")]
+    pub size: Option<u32>,
+}
+
+impl Default for IconStyle {
+    fn default() -> Self {
+        Self {
+            mode: IconMode::Text,
+            font: FontChoice::default(),
+            glyph_number: false,
+            theme: ThemeMode::Auto,
+            text: "{pct}{charging:*}{not-charging:~}{unknown:?}".parse().unwrap(),
+            power_text: "{power}".parse().unwrap(),
+            full_text: "^_^".to_string(),
+            full_threshold: 97,
+            light: Theme::LIGHT,
+            dark: Theme::DARK,
+            colors: ColorRamp::default(),
+            size: None,
+        }
+    }
+}
+
+impl IconStyle {
+    /// 根据系统外观选出当前使用的配色
+    pub fn resolve_theme(&self, system: Appearance) -> Theme {
+        let appearance = match self.theme {
+            ThemeMode::Auto => system,
+            ThemeMode::Light => Appearance::Light,
+            ThemeMode::Dark => Appearance::Dark,
+        };
+        match appearance {
+            Appearance::Light => self.light,
+            Appearance::Dark => self.dark,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.full_threshold <= 100,
+            "icon.full_threshold must be between 0 and 100, got {}",
+            self.full_threshold
+        );
+        ensure!(!self.full_text.is_empty(), "icon.full_text must not be empty");
+        if let Some(size) = self.size {
+            ensure!((16..=256).contains(&size), "icon.size must be between 16 and 256, got {}", size);
+        }
+        self.colors.validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_parses_and_displays() {
+        let cases = [
+            ("#2e9d3a", Color([0x2e, 0x9d, 0x3a, 255]), "#2e9d3a"),
+            ("2E9D3A", Color([0x2e, 0x9d, 0x3a, 255]), "#2e9d3a"),
+            ("#00000080", Color([0, 0, 0, 0x80]), "#00000080"),
+            ("#ffffffff", Color::WHITE, "#ffffff"),
+        ];
+        for (input, color, display) in cases {
+            assert_eq!(input.parse::<Color>().unwrap(), color, "{}", input);
+            assert_eq!(color.to_string(), display);
+            assert_eq!(display.parse::<Color>().unwrap(), color);
+        }
+        for invalid in ["", "#fff", "#12345", "#1234567", "#gg0000", "#ff00ff0", "#ff00ff+1"] {
+            assert!(invalid.parse::<Color>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn icon_mode_round_trips() {
+        for mode in IconMode::ALL {
+            assert_eq!(mode.as_str().parse::<IconMode>().unwrap(), mode);
+            let style: IconStyle = toml::from_str(&format!("mode = \"{}\"", mode.as_str())).unwrap();
+            assert_eq!(style.mode, mode);
+        }
+        assert!("Glyph".parse::<IconMode>().is_err());
+        assert!(toml::from_str::<IconStyle>("mode = \"bar\"").is_err());
+    }
+
+    #[test]
+    fn color_ramp_picks_color_by_level() {
+        let ramp = ColorRamp { enabled: true, ..ColorRamp::default() };
+        let cases = [
+            (80, ChargeState::Discharging, ramp.high_color),
+            (50, ChargeState::Discharging, ramp.medium_color),
+            (21, ChargeState::Discharging, ramp.medium_color),
+            (20, ChargeState::Discharging, ramp.low_color),
+            (5, ChargeState::Charging, ramp.charging_color),
+            (90, ChargeState::NotCharging, ramp.charging_color),
+            (100, ChargeState::Full, ramp.high_color),
+        ];
+        for (percentage, state, expected) in cases {
+            assert_eq!(ramp.color_for(percentage, state), Some(expected), "{} {:?}", percentage, state);
+        }
+        assert_eq!(ColorRamp::default().color_for(10, ChargeState::Discharging), None);
+    }
+
+    #[test]
+    fn color_ramp_validates_thresholds() {
+        assert!(ColorRamp::default().validate().is_ok());
+        assert!(ColorRamp { low: 50, ..ColorRamp::default() }.validate().is_err());
+        assert!(ColorRamp { high: 101, ..ColorRamp::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn icon_style_parses_partial_sections() {
+        let style: IconStyle = toml::from_str(
+            r##"
+            size = 0
+            [dark]
+            foreground = "#eeeeee"
+            outline = "#00000080"
+            [colors]
+            enabled = true
+            low = 15
+            "##,
+        )
+        .unwrap();
+        assert_eq!(style.size, None);
+        assert_eq!(style.dark.foreground, Color([0xee, 0xee, 0xee, 255]));
+        assert_eq!(style.dark.background, None);
+        assert_eq!(style.dark.outline, Some(Color([0, 0, 0, 0x80])));
+        assert_eq!(style.colors.low, 15);
+        assert_eq!(style.colors.high, ColorRamp::default().high);
+        assert_eq!(style.light, Theme::LIGHT);
+    }
+
+    #[test]
+    fn icon_style_rejects_unknown_fields() {
+        let inputs = [
+            "colour = \"#ffffff\"",
+            "[colors]\nmedium = 30",
+            "[light]\nforeground = \"#000000\"\nshadow = \"#808080\"",
+        ];
+        for input in inputs {
+            assert!(toml::from_str::<IconStyle>(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn icon_style_round_trips() {
+        let style = IconStyle {
+            mode: IconMode::Glyph,
+            glyph_number: true,
+            theme: ThemeMode::Dark,
+            size: Some(32),
+            dark: Theme {
+                foreground: Color([0xee, 0xee, 0xee, 255]),
+                background: Some(Color([0x20, 0x20, 0x20, 0xc0])),
+                outline: None,
+            },
+            colors: ColorRamp { enabled: true, ..ColorRamp::default() },
+            ..IconStyle::default()
+        };
+        let parsed: IconStyle = toml::from_str(&toml::to_string(&style).unwrap()).unwrap();
+        assert_eq!(parsed, style);
+    }
+}
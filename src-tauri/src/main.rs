@@ -1,139 +1,255 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod aggregation;
+mod battery_glyph;
 mod battery_icon_generator;
-use battery_icon_generator::BatteryIconGenerator;
+mod battery_source;
+mod change_watcher;
+mod cli;
+mod commands;
+mod device_trays;
+#[cfg(target_os = "linux")]
+mod dbus_service;
+mod estimator;
+mod fonts;
+mod health;
+mod history;
+mod http_server;
+mod icon_style;
+mod notifier;
+mod peripherals;
+mod settings;
+mod status;
+mod system_theme;
+mod template;
+mod tray_menu;
+mod tray_updater;
+mod windows;
+use battery_source::{BatterySource, SourceKind, SourceUpdate};
+use change_watcher::{Backoff, RefreshRequest};
+use history::HistoryStore;
+use peripherals::{PeripheralSourceKind, Peripherals};
+use settings::{Settings, SettingsStore};
+use tray_updater::spawn_tray_updater;
 
 use std::{sync::Arc, thread, time::Duration};
 use anyhow::{Context, Result};
-use battery::{Manager, State};
-use tauri::{
-    async_runtime, menu::{Menu, MenuItem}, tray::{TrayIcon, TrayIconBuilder}, App, AppHandle, Wry
-};
-use tokio::sync::{
-    mpsc::{channel, Receiver, Sender},
-    Mutex,
-};
+use tauri::{async_runtime, tray::TrayIconBuilder, App, AppHandle, Manager, RunEvent};
+use tokio::sync::{watch, Mutex};
 use tauri_plugin_autostart::MacosLauncher;
-use tauri_plugin_autostart::ManagerExt;
 
-/// 在独立线程中定期读取电池电量并发送消息
-fn spawn_battery_monitor(tx: Sender<(u32, State)>) {
+/// 在独立线程中读取 select 选出的数据源，有变化时发送消息；数据源设置变化时重新打开数据源。
+/// 数据源无法打开时把错误发给托盘，并按 Backoff 间隔重试；open 返回 None（数据源未启用）时
+/// 发送空读数并等待设置变化。收到 refresh 请求时中断等待，立即重新读取
+fn spawn_monitor<K: PartialEq + Send + 'static>(
+    select: impl Fn(&Settings) -> K + Send + 'static,
+    open: impl Fn(&K) -> Result<Option<Box<dyn BatterySource>>> + Send + 'static,
+    mut settings_rx: watch::Receiver<Settings>,
+    tx: Arc<watch::Sender<Option<SourceUpdate>>>,
+    refresh: RefreshRequest,
+) {
     thread::spawn(move || {
-        let manager = Manager::new().expect("Failed to initialize battery manager");
-        let mut last_battery_info: Option<(u32, State)> = None;
+        let mut settings = settings_rx.borrow_and_update().clone();
+        let mut kind = select(&settings);
+        let mut source: Option<Box<dyn BatterySource>> = None;
+        let mut backoff = Backoff::new(settings.monitor.poll_interval);
 
-        loop {
-            if let Ok(batteries) = manager.batteries() {
-                if let Some(battery) = batteries.flatten().next() {
-                    let percentage = (battery.state_of_charge().value * 100.0).round() as u32;
-                    let state = battery.state();
-
-                    if Some((percentage, state)) != last_battery_info {
-                        last_battery_info = Some((percentage, state));
-
-                        tx.blocking_send((percentage, state))
-                            .expect("Failed to send battery info");
+        while !tx.is_closed() {
+            refresh.take();
+            let interrupted = || settings_rx.has_changed().unwrap_or(false) || refresh.pending();
+            let result = match &mut source {
+                Some(source) => source.subscribe(&settings.monitor, &tx, &interrupted),
+                source => open(&kind).map(|opened| match opened {
+                    Some(opened) => {
+                        backoff.reset();
+                        *source = Some(opened);
                     }
+                    None => {
+                        // 数据源未启用：报告为空，直到设置变化
+                        SourceUpdate::Readings(Vec::new()).publish(&tx);
+                        while !interrupted() && !tx.is_closed() {
+                            thread::sleep(Duration::from_millis(500));
+                        }
+                    }
+                }),
+            };
+            if let Err(e) = result {
+                eprintln!("Battery monitor error: {:#}", e);
+                source = None;
+                SourceUpdate::Error(format!("{:#}", e)).publish(&tx);
+                backoff.wait(&interrupted);
+            }
+
+            if settings_rx.has_changed().unwrap_or(false) {
+                settings = settings_rx.borrow_and_update().clone();
+                if select(&settings) != kind {
+                    kind = select(&settings);
+                    source = None;
+                    backoff = Backoff::new(settings.monitor.poll_interval);
                 }
             }
-            thread::sleep(Duration::from_secs(1));
         }
     });
 }
 
-fn init_menu(app: &AppHandle) -> Result<Menu<Wry>, tauri::Error> {
-    let autostart_status = app
-        .autolaunch()
-        .is_enabled()
-        .unwrap_or(false);
-    let autostart_item = MenuItem::with_id(
-        app,
-        "autostart",
-        format!("{} autostart", if autostart_status { "Disable" } else { "Enable" }),
-        true,
-        None::<&str>,
-    )?;
-    let quit_item = MenuItem::with_id(
-        app, 
-        "quit", 
-        "Quit", 
-        true, 
-        None::<&str>
-    )?;
-    Menu::with_items(app, &[&autostart_item, &quit_item])
+/// 读取设置，失败时使用默认设置并提示用户，修正配置文件后会自动生效
+fn init_settings(app: &AppHandle) -> Arc<SettingsStore> {
+    let path = match SettingsStore::default_path() {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("Failed to locate settings file, using defaults: {:#}", e);
+            notifier::show_message(app, "Settings won't be saved", &format!("{:#}", e));
+            return Arc::new(SettingsStore::in_memory(Settings::default()));
+        }
+    };
+    let store = match SettingsStore::open(path.clone()) {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("Failed to load settings, using defaults: {:#}", e);
+            notifier::show_message(app, "Invalid settings", &format!("{:#}", e));
+            SettingsStore::new(path, Settings::default())
+        }
+    };
+    let store = Arc::new(store);
+
+    let handle = app.clone();
+    let watched = store.clone().watch(move |e| {
+        eprintln!("Failed to reload settings: {:#}", e);
+        notifier::show_message(&handle, "Invalid settings", &format!("{:#}", e));
+    });
+    // 无法监听时仍可运行，只是修改配置文件后需要重启
+    if let Err(e) = watched {
+        eprintln!("Failed to watch settings, changes need a restart: {:#}", e);
+        notifier::show_message(app, "Settings won't reload automatically", &format!("{:#}", e));
+    }
+
+    store
 }
 
 /// 初始化托盘图标和菜单
 fn init_tray(app: &mut App) -> Result<()> {
-    let tray_icon = TrayIconBuilder::with_id("tray_id")
-        .menu(&init_menu(app.handle())?)
+    let store = init_settings(app.handle());
+    app.manage(store.clone());
+    let history = match HistoryStore::default_path() {
+        Ok(path) => HistoryStore::new(path),
+        Err(e) => {
+            eprintln!("Failed to locate history file, history disabled: {:#}", e);
+            notifier::show_message(app.handle(), "History unavailable", &format!("{:#}", e));
+            HistoryStore::unavailable()
+        }
+    };
+    let history = Arc::new(history);
+    app.manage(history.clone());
+
+    // 托盘菜单列出外设，需要在创建托盘前准备好
+    let (peripherals_tx, peripherals_rx) = watch::channel(None);
+    app.manage(Peripherals::new(peripherals_rx.clone()));
+
+    let tray_icon = TrayIconBuilder::with_id(tray_menu::TRAY_ID)
+        .menu(&tray_menu::init_menu(app.handle())?)
         .build(app)?;
     let tray = Arc::new(Mutex::new(tray_icon));
 
-    let (tx, rx) = channel(1);
-    spawn_battery_monitor(tx);
-    spawn_tray_updater(tray, rx);
+    let (tx, rx) = watch::channel(None);
+    let tx = Arc::new(tx);
+    let refresh = RefreshRequest::default();
+    let source_override = SourceKind::from_env().unwrap_or_else(|e| {
+        eprintln!("Ignoring the battery source override: {:#}", e);
+        notifier::show_message(app.handle(), "Invalid battery source", &format!("{:#}", e));
+        None
+    });
+    spawn_monitor(
+        move |settings| source_override.clone().unwrap_or_else(|| settings.source.clone()),
+        |kind| kind.open().map(Some),
+        store.subscribe(),
+        tx.clone(),
+        refresh.clone(),
+    );
+    spawn_monitor(
+        |settings| settings.peripherals.source.clone(),
+        PeripheralSourceKind::open,
+        store.subscribe(),
+        Arc::new(peripherals_tx),
+        RefreshRequest::default(),
+    );
+    let appearance_rx = system_theme::spawn_appearance_watcher();
+    spawn_tray_updater(
+        app.handle().clone(),
+        tray,
+        rx.clone(),
+        peripherals_rx.clone(),
+        store.subscribe(),
+        appearance_rx.clone(),
+    );
+    device_trays::spawn_device_trays(
+        app.handle().clone(),
+        rx.clone(),
+        peripherals_rx,
+        store.subscribe(),
+        appearance_rx,
+    );
 
-    Ok(())
-}
+    let handle = app.handle().clone();
+    async_runtime::spawn(http_server::run_http_server(store.subscribe(), rx, history, move |e| {
+        eprintln!("HTTP server stopped: {:#}", e);
+        notifier::show_message(&handle, "HTTP server unavailable", &format!("{:#}", e));
+    }));
 
-/// 启动异步任务监听电池更新并修改托盘图标
-fn spawn_tray_updater(tray: Arc<Mutex<TrayIcon>>, mut rx: Receiver<(u32, State)>) {
+    #[cfg(target_os = "linux")]
     async_runtime::spawn(async move {
-        let icon_generator = BatteryIconGenerator::new().unwrap();
-        while let Some((percentage, state)) = rx.recv().await {
-            if let Ok(icon) = icon_generator.generate_icon(percentage, state == State::Charging).await {
-                let tooltip = match state {
-                    State::Charging => format!("Charging: {}%", percentage),
-                    State::Discharging => format!("Discharging: {}%", percentage),
-                    State::Full => format!("Full"),
-                    _ => format!("Unhandled state: {}%", percentage),
-                };
-
-                let tray = tray.lock().await;
-                if let Err(e) = tray.set_icon(Some(icon)) {
-                    eprintln!("Failed to update tray icon: {}", e);
-                }
-                if let Err(e) = tray.set_tooltip(Some(&tooltip)) {
-                    eprintln!("Failed to update tray tooltip: {}", e);
-                }
-            }
+        if let Err(e) = dbus_service::run_dbus_service(store, tx.subscribe(), refresh).await {
+            eprintln!("D-Bus service stopped: {:#}", e);
         }
     });
+
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() {
+    // 带子命令时作为命令行工具运行，完成后直接退出
+    match cli::parse(std::env::args().skip(1)) {
+        Ok(None) => {}
+        Ok(Some(command)) => {
+            cli::attach_console();
+            if let Err(e) = cli::run(command) {
+                eprintln!("Error: {:#}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Err(e) => {
+            cli::attach_console();
+            eprintln!("Error: {:#}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    }
+
     tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
         .setup(|app| {
             app.handle().plugin(tauri_plugin_autostart::init(
                 MacosLauncher::LaunchAgent,
                 None,
             )).context("Error initializing autostart plugin")?;
+            app.handle().plugin(tauri_plugin_notification::init())
+                .context("Error initializing notification plugin")?;
 
             init_tray(app)?;
             Ok(())
         })
-        .on_menu_event(|app, event| match event.id.as_ref() {
-            "quit" => {
-                println!("User clicked quit");
-                app.exit(0);
+        .on_menu_event(tray_menu::handle_menu_event)
+        .invoke_handler(tauri::generate_handler![
+            commands::get_history,
+            commands::get_health_report,
+            commands::export_health_report,
+        ])
+        .build(tauri::generate_context!())
+        .expect("Error building Tauri app")
+        .run(|_app, event| {
+            // 关闭最后一个窗口时继续驻留托盘，只有菜单中的 Quit 会退出
+            if let RunEvent::ExitRequested { code: None, api, .. } = event {
+                api.prevent_exit();
             }
-            "autostart" => {
-                let autostart = app.autolaunch();
-                let enabled = autostart.is_enabled().unwrap_or(false);
-                if enabled {
-                    let _ = autostart.disable();
-                } else {
-                    let _ = autostart.enable();
-                }
-                let tray = app.tray_by_id("tray_id").expect("Failed to get tray handle");
-                tray.set_menu(init_menu(app).ok()).expect("Failed to update tray menu");
-            }
-            other => {
-                println!("Unhandled menu item: {:?}", other);
-            }
-        })
-        .run(tauri::generate_context!())
-        .expect("Error running Tauri app");
+        });
 }
@@ -0,0 +1,290 @@
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::{
+    battery_source::{BatteryReading, ChargeState},
+    settings::zero_as_none,
+};
+
+/// 电量提醒阈值，为 None 时关闭对应提醒（配置文件中写 0）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifierConfig {
+    /// 低电量提醒
+    #[serde(with = "zero_as_none")]
+    pub low: Option<u32>,
+    /// 严重低电量提醒
+    #[serde(with = "zero_as_none")]
+    pub critical: Option<u32>,
+    /// 充电到指定电量时提醒拔掉电源，保护电池。默认关闭
+    #[serde(with = "zero_as_none")]
+    pub charged: Option<u32>,
+    /// 电量回到阈值另一侧多少后才允许再次提醒，避免在阈值附近反复弹出
+    pub hysteresis: u32,
+}
+
+impl Default for NotifierConfig {
+    fn default() -> Self {
+        Self {
+            low: Some(20),
+            critical: Some(10),
+            charged: None,
+            hysteresis: 2,
+        }
+    }
+}
+
+impl NotifierConfig {
+    pub fn validate(&self) -> Result<()> {
+        for (name, threshold) in [("low", self.low), ("critical", self.critical), ("charged", self.charged)] {
+            if let Some(threshold) = threshold {
+                ensure!(
+                    threshold <= 100,
+                    "notifications.{} must be between 0 and 100, got {}",
+                    name,
+                    threshold
+                );
+            }
+        }
+        if let (Some(low), Some(critical)) = (self.low, self.critical) {
+            ensure!(
+                critical < low,
+                "notifications.critical ({}) must be lower than notifications.low ({})",
+                critical,
+                low
+            );
+        }
+        ensure!(self.hysteresis <= 20, "notifications.hysteresis must not exceed 20");
+        Ok(())
+    }
+}
+
+/// 提醒类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alert {
+    Low,
+    Critical,
+    Charged,
+}
+
+impl Alert {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Alert::Low => "Battery low",
+            Alert::Critical => "Battery critically low",
+            Alert::Charged => "Battery charged",
+        }
+    }
+
+    pub fn body(&self, percentage: u32) -> String {
+        match self {
+            Alert::Low => format!("{}% remaining. Consider plugging in.", percentage),
+            Alert::Critical => format!("{}% remaining. Plug in now to avoid losing work.", percentage),
+            Alert::Charged => format!("Charged to {}%. Unplug to extend battery life.", percentage),
+        }
+    }
+}
+
+/// 记录各提醒是否已触发，每次穿越阈值只提醒一次
+#[derive(Debug, Default)]
+pub struct AlertTracker {
+    low_fired: bool,
+    critical_fired: bool,
+    charged_fired: bool,
+}
+
+impl AlertTracker {
+    /// 根据最新读数返回需要发出的提醒
+    pub fn check(&mut self, config: &NotifierConfig, reading: &BatteryReading) -> Option<Alert> {
+        let percentage = reading.percentage;
+        let discharging = reading.state == ChargeState::Discharging;
+        let charging = reading.state.is_plugged_in();
+
+        // 回到阈值以上（加上回差）后重新允许提醒
+        let rearm_above = |threshold: Option<u32>| threshold.is_none_or(|t| percentage >= t + config.hysteresis);
+        if rearm_above(config.low) {
+            self.low_fired = false;
+        }
+        if rearm_above(config.critical) {
+            self.critical_fired = false;
+        }
+        if config.charged.is_none_or(|t| percentage + config.hysteresis <= t) {
+            self.charged_fired = false;
+        }
+
+        let crossed = |threshold: Option<u32>| threshold.is_some_and(|t| percentage <= t);
+        if discharging && crossed(config.critical) && !self.critical_fired {
+            // 严重低电量包含了低电量提醒
+            self.critical_fired = true;
+            self.low_fired = true;
+            return Some(Alert::Critical);
+        }
+        if discharging && crossed(config.low) && !self.low_fired {
+            self.low_fired = true;
+            return Some(Alert::Low);
+        }
+        if charging && config.charged.is_some_and(|t| percentage >= t) && !self.charged_fired {
+            self.charged_fired = true;
+            return Some(Alert::Charged);
+        }
+
+        None
+    }
+}
+
+/// 发送电量提醒
+pub fn show_alert<R: Runtime>(app: &AppHandle<R>, alert: Alert, percentage: u32) {
+    show_message(app, alert.title(), &alert.body(percentage));
+}
+
+/// 发送系统通知
+pub fn show_message<R: Runtime>(app: &AppHandle<R>, title: &str, body: &str) {
+    if let Err(e) = app
+        .notification()
+        .builder()
+        .title(title)
+        .body(body)
+        .show()
+    {
+        eprintln!("Failed to show notification: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ChargeState::{Charging, Discharging, Full};
+
+    /// 一步读数：百分比、状态和期望的提醒
+    type Step = (u32, ChargeState, Option<Alert>);
+
+    /// 依次检查读数序列
+    fn run(name: &str, config: &NotifierConfig, steps: &[Step]) {
+        let mut tracker = AlertTracker::default();
+        for (i, &(percentage, state, expected)) in steps.iter().enumerate() {
+            let reading = BatteryReading::new("BAT0", percentage, state);
+            assert_eq!(
+                tracker.check(config, &reading),
+                expected,
+                "{}: step {} ({}% {:?})",
+                name,
+                i,
+                percentage,
+                state
+            );
+        }
+    }
+
+    #[test]
+    fn check_follows_thresholds() {
+        let cases: &[(&str, &[Step])] = &[
+            (
+                "discharge crosses low then critical",
+                &[
+                    (25, Discharging, None),
+                    (20, Discharging, Some(Alert::Low)),
+                    (19, Discharging, None),
+                    (11, Discharging, None),
+                    (10, Discharging, Some(Alert::Critical)),
+                    (5, Discharging, None),
+                ],
+            ),
+            (
+                "bouncing inside the hysteresis band",
+                &[
+                    (20, Discharging, Some(Alert::Low)),
+                    (21, Discharging, None),
+                    (20, Discharging, None),
+                    (21, Charging, None),
+                    (20, Discharging, None),
+                    (22, Charging, None),
+                    (20, Discharging, Some(Alert::Low)),
+                ],
+            ),
+            (
+                "charged alert re-arms after dropping below the band",
+                &[
+                    (79, Charging, None),
+                    (80, Charging, Some(Alert::Charged)),
+                    (85, Charging, None),
+                    (79, Full, None),
+                    (80, Charging, None),
+                    (78, Discharging, None),
+                    (80, Charging, Some(Alert::Charged)),
+                ],
+            ),
+            (
+                "critical implies low",
+                &[
+                    (8, Discharging, Some(Alert::Critical)),
+                    (9, Discharging, None),
+                    (15, Discharging, None),
+                ],
+            ),
+            (
+                "each threshold re-arms on its own",
+                &[
+                    (10, Discharging, Some(Alert::Critical)),
+                    (11, Charging, None),
+                    (10, Discharging, None),
+                    (12, Charging, None),
+                    (10, Discharging, Some(Alert::Critical)),
+                    (22, Charging, None),
+                    (20, Discharging, Some(Alert::Low)),
+                ],
+            ),
+            (
+                "no alerts while plugged in below low",
+                &[(5, Charging, None), (15, Full, None)],
+            ),
+        ];
+
+        let config = NotifierConfig {
+            charged: Some(80),
+            ..NotifierConfig::default()
+        };
+        for (name, steps) in cases {
+            run(name, &config, steps);
+        }
+    }
+
+    #[test]
+    fn charged_alert_is_off_by_default() {
+        run(
+            "default config",
+            &NotifierConfig::default(),
+            &[(80, Charging, None), (100, Charging, None), (100, Full, None)],
+        );
+    }
+
+    #[test]
+    fn check_skips_disabled_thresholds() {
+        let config = NotifierConfig {
+            low: None,
+            charged: None,
+            ..NotifierConfig::default()
+        };
+        run(
+            "disabled thresholds",
+            &config,
+            &[
+                (15, Discharging, None),
+                (10, Discharging, Some(Alert::Critical)),
+                (100, Charging, None),
+            ],
+        );
+    }
+
+    #[test]
+    fn validate_rejects_inverted_thresholds() {
+        let config = NotifierConfig {
+            low: Some(10),
+            critical: Some(20),
+            ..NotifierConfig::default()
+        };
+        assert!(config.validate().is_err());
+        assert!(NotifierConfig::default().validate().is_ok());
+    }
+}
@@ -0,0 +1,129 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+use anyhow::{bail, ensure, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+#[cfg(target_os = "linux")]
+use crate::battery_source::UpowerSource;
+use crate::battery_source::{BatteryReading, BatterySource, SourceUpdate, SysfsSource};
+
+/// 蓝牙鼠标、键盘、耳机等外设电量的设置
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PeripheralConfig {
+    /// 外设数据源，如 `sysfs`、`upower`，`none` 表示不读取外设
+    pub source: PeripheralSourceKind,
+    /// 代替电池显示在托盘图标上的外设名称，该外设不在线时仍显示电池
+    pub pinned: Option<String>,
+}
+
+impl PeripheralConfig {
+    pub fn validate(&self) -> Result<()> {
+        ensure!(self.pinned.as_deref() != Some(""), "peripherals.pinned must not be empty");
+        Ok(())
+    }
+}
+
+/// 外设数据源类型，Linux 上默认读取 sysfs，其他平台默认不读取
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum PeripheralSourceKind {
+    None,
+    /// Linux sysfs 目录中 scope 为 Device 的电池
+    Sysfs(PathBuf),
+    /// Linux 上 UPower 报告的外设
+    Upower,
+}
+
+impl Default for PeripheralSourceKind {
+    fn default() -> Self {
+        if cfg!(target_os = "linux") {
+            PeripheralSourceKind::Sysfs(PathBuf::from(SysfsSource::DEFAULT_ROOT))
+        } else {
+            PeripheralSourceKind::None
+        }
+    }
+}
+
+impl PeripheralSourceKind {
+    /// 创建对应的数据源，none 返回 None
+    pub fn open(&self) -> Result<Option<Box<dyn BatterySource>>> {
+        let source: Box<dyn BatterySource> = match self {
+            PeripheralSourceKind::None => return Ok(None),
+            PeripheralSourceKind::Sysfs(path) => Box::new(SysfsSource::peripherals(path)),
+            #[cfg(target_os = "linux")]
+            PeripheralSourceKind::Upower => Box::new(UpowerSource::peripherals()?),
+            #[cfg(not(target_os = "linux"))]
+            PeripheralSourceKind::Upower => bail!("The upower peripheral source is only available on Linux"),
+        };
+        Ok(Some(source))
+    }
+}
+
+impl FromStr for PeripheralSourceKind {
+    type Err = anyhow::Error;
+
+    /// 格式为 `none`、`sysfs[:目录]` 或 `upower`
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, arg) = match s.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg)),
+            None => (s, None),
+        };
+        match (kind.trim(), arg) {
+            ("none", None) => Ok(PeripheralSourceKind::None),
+            ("sysfs", arg) => Ok(PeripheralSourceKind::Sysfs(
+                arg.map(PathBuf::from)
+                    .unwrap_or_else(|| PathBuf::from(SysfsSource::DEFAULT_ROOT)),
+            )),
+            ("upower", None) => Ok(PeripheralSourceKind::Upower),
+            _ => bail!("Unknown peripheral source: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for PeripheralSourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeripheralSourceKind::None => write!(f, "none"),
+            PeripheralSourceKind::Sysfs(path) => write!(f, "sysfs:{}", path.display()),
+            PeripheralSourceKind::Upower => write!(f, "upower"),
+        }
+    }
+}
+
+impl TryFrom<String> for PeripheralSourceKind {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<PeripheralSourceKind> for String {
+    fn from(kind: PeripheralSourceKind) -> Self {
+        kind.to_string()
+    }
+}
+
+/// 外设监视器的最新读数，托盘菜单与托盘图标从这里读取
+pub struct Peripherals(watch::Receiver<Option<SourceUpdate>>);
+
+impl Peripherals {
+    pub fn new(rx: watch::Receiver<Option<SourceUpdate>>) -> Self {
+        Self(rx)
+    }
+
+    /// 当前在线的外设，尚未读到数据或数据源出错时为空
+    pub fn readings(&self) -> Vec<BatteryReading> {
+        match &*self.0.borrow() {
+            Some(SourceUpdate::Readings(readings)) => readings.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// 固定在托盘图标上的外设，不在线时返回 None
+    pub fn pinned(&self, config: &PeripheralConfig) -> Option<BatteryReading> {
+        let name = config.pinned.as_ref()?;
+        self.readings().into_iter().find(|reading| &reading.name == name)
+    }
+}
@@ -0,0 +1,362 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+    thread,
+    time::Duration,
+};
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::{
+    aggregation::Aggregation, battery_source::SourceKind, change_watcher::MonitorConfig,
+    device_trays::TrayConfig, history::HistoryConfig, http_server::HttpConfig, icon_style::IconStyle, notifier::NotifierConfig,
+    peripherals::PeripheralConfig, template::TooltipConfig,
+};
+
+/// 用户设置，保存在配置目录的 settings.toml 中，缺省的字段使用默认值
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// 电池数据源，如 `system`、`sysfs:/sys/class/power_supply`
+    pub source: SourceKind,
+    pub aggregation: Aggregation,
+    pub monitor: MonitorConfig,
+    pub notifications: NotifierConfig,
+    pub icon: IconStyle,
+    pub tooltip: TooltipConfig,
+    pub history: HistoryConfig,
+    pub http: HttpConfig,
+    pub peripherals: PeripheralConfig,
+    pub tray: TrayConfig,
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<()> {
+        self.monitor.validate()?;
+        self.notifications.validate()?;
+        self.icon.validate()?;
+        self.history.validate()?;
+        self.http.validate()?;
+        self.peripherals.validate()?;
+        self.tray.validate()?;
+        Ok(())
+    }
+
+    /// 读取配置文件，文件不存在时返回默认设置，不会创建文件
+    pub fn load_or_default(path: &Path) -> Result<Self> {
+        if path.exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let settings: Settings = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        settings
+            .validate()
+            .with_context(|| format!("Invalid settings in {}", path.display()))?;
+        Ok(settings)
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let content = toml::to_string_pretty(self).context("Failed to serialize settings")?;
+        fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// 设置的持有者：负责读写配置文件，并通过 watch 通道广播最新设置
+pub struct SettingsStore {
+    /// 找不到配置目录时为 None，设置只保存在内存中
+    path: Option<PathBuf>,
+    tx: watch::Sender<Settings>,
+}
+
+impl SettingsStore {
+    /// 合并文件系统事件的等待时间，编辑器保存时通常会连续产生多个事件
+    const DEBOUNCE: Duration = Duration::from_millis(200);
+
+    /// 平台配置目录下的 percentage-rust/settings.toml
+    pub fn default_path() -> Result<PathBuf> {
+        let dir = dirs::config_dir().context("Failed to locate config directory")?;
+        Ok(dir.join("percentage-rust").join("settings.toml"))
+    }
+
+    /// 使用给定设置创建，不读取文件
+    pub fn new(path: PathBuf, settings: Settings) -> Self {
+        Self {
+            path: Some(path),
+            tx: watch::channel(settings).0,
+        }
+    }
+
+    /// 不对应任何文件的设置，在无法确定配置文件位置时使用，修改在退出后丢失
+    pub fn in_memory(settings: Settings) -> Self {
+        Self {
+            path: None,
+            tx: watch::channel(settings).0,
+        }
+    }
+
+    /// 读取配置文件，文件不存在时写入默认设置
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let settings = if path.exists() {
+            Settings::load(&path)?
+        } else {
+            let settings = Settings::default();
+            settings.save(&path)?;
+            settings
+        };
+        Ok(Self::new(path, settings))
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn get(&self) -> Settings {
+        self.tx.borrow().clone()
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Settings> {
+        self.tx.subscribe()
+    }
+
+    /// 与电池监视器相同的数据源：环境变量优先，其次是设置
+    pub fn source(&self) -> SourceKind {
+        SourceKind::from_env()
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| self.get().source)
+    }
+
+    /// 修改设置并写回文件
+    pub fn update(&self, f: impl FnOnce(&mut Settings)) -> Result<()> {
+        let mut settings = self.get();
+        f(&mut settings);
+        settings.validate()?;
+        if let Some(path) = &self.path {
+            settings.save(path)?;
+        }
+        self.publish(settings);
+        Ok(())
+    }
+
+    /// 重新读取配置文件，出错时保留当前设置
+    pub fn reload(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let settings = Settings::load(path)?;
+        self.publish(settings);
+        Ok(())
+    }
+
+    /// 只有设置确实变化时才通知订阅者
+    fn publish(&self, settings: Settings) {
+        self.tx.send_if_modified(|current| {
+            if *current == settings {
+                return false;
+            }
+            *current = settings;
+            true
+        });
+    }
+
+    /// 在后台线程监听配置文件，变化时自动重新加载，出错时调用 on_error
+    pub fn watch(self: Arc<Self>, on_error: impl Fn(anyhow::Error) + Send + 'static) -> Result<()> {
+        let path = self.path.clone().context("Settings are not stored in a file")?;
+        let dir = path
+            .parent()
+            .context("Settings path has no parent directory")?
+            .to_path_buf();
+
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx).context("Failed to create file watcher")?;
+        // 监听所在目录而不是文件本身，编辑器常用“写临时文件再重命名”的方式保存
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {}", dir.display()))?;
+
+        thread::spawn(move || {
+            // watcher 被丢弃时会停止监听，需要让它与线程同生命周期
+            let _watcher = watcher;
+
+            while let Ok(event) = rx.recv() {
+                let relevant = match event {
+                    Ok(event) => {
+                        matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                            && event.paths.iter().any(|p| p == &path)
+                    }
+                    Err(e) => {
+                        eprintln!("Settings watcher error: {}", e);
+                        false
+                    }
+                };
+                if !relevant {
+                    continue;
+                }
+
+                thread::sleep(Self::DEBOUNCE);
+                rx.try_iter().for_each(drop);
+
+                if let Err(e) = self.reload() {
+                    on_error(e);
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// 在配置文件中以秒数表示 Duration
+pub mod duration_secs {
+    use std::time::Duration;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let secs = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(secs).map_err(D::Error::custom)
+    }
+}
+
+/// 配置文件无法表示 None，用 0 表示关闭
+pub mod zero_as_none {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<u32>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(value.unwrap_or(0))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+        Ok(Some(u32::deserialize(deserializer)?).filter(|&v| v > 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Fields {
+        #[serde(with = "duration_secs")]
+        interval: Duration,
+        #[serde(with = "zero_as_none")]
+        limit: Option<u32>,
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir()
+            .join(format!("percentage-rust-settings-{}-{}", name, std::process::id()))
+            .join("settings.toml")
+    }
+
+    #[test]
+    fn duration_secs_accepts_fractions() {
+        let cases = [
+            ("interval = 2\nlimit = 1", Duration::from_secs(2)),
+            ("interval = 0.5\nlimit = 1", Duration::from_millis(500)),
+            ("interval = 1.25\nlimit = 1", Duration::from_millis(1250)),
+        ];
+        for (input, expected) in cases {
+            let fields: Fields = toml::from_str(input).unwrap();
+            assert_eq!(fields.interval, expected, "{}", input);
+        }
+        assert!(toml::from_str::<Fields>("interval = -1.0\nlimit = 1").is_err());
+        assert!(toml::from_str::<Fields>("interval = \"1s\"\nlimit = 1").is_err());
+    }
+
+    #[test]
+    fn zero_as_none_maps_zero_to_off() {
+        let fields: Fields = toml::from_str("interval = 1\nlimit = 0").unwrap();
+        assert_eq!(fields.limit, None);
+        let fields: Fields = toml::from_str("interval = 1\nlimit = 20").unwrap();
+        assert_eq!(fields.limit, Some(20));
+        assert!(toml::from_str::<Fields>("interval = 1\nlimit = -1").is_err());
+    }
+
+    #[test]
+    fn fields_round_trip() {
+        for fields in [
+            Fields { interval: Duration::from_millis(1500), limit: None },
+            Fields { interval: Duration::from_secs(300), limit: Some(80) },
+        ] {
+            let content = toml::to_string(&fields).unwrap();
+            assert_eq!(toml::from_str::<Fields>(&content).unwrap(), fields, "{}", content);
+        }
+    }
+
+    #[test]
+    fn default_settings_round_trip() {
+        let content = toml::to_string_pretty(&Settings::default()).unwrap();
+        let parsed: Settings = toml::from_str(&content).unwrap();
+        assert_eq!(parsed, Settings::default());
+        parsed.validate().unwrap();
+    }
+
+    #[test]
+    fn empty_file_uses_defaults() {
+        assert_eq!(toml::from_str::<Settings>("").unwrap(), Settings::default());
+        let settings: Settings = toml::from_str("[notifications]\nlow = 0\n[monitor]\npoll_interval_secs = 0.5").unwrap();
+        assert_eq!(settings.notifications.low, None);
+        assert_eq!(settings.monitor.poll_interval, Duration::from_millis(500));
+        assert_eq!(settings.icon, IconStyle::default());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        for input in ["colour = \"red\"", "[icons]\nmode = \"text\"", "[monitor]\npoll_interval = 1", "[history]\nretention = 7"] {
+            assert!(toml::from_str::<Settings>(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn store_writes_defaults_and_saves_updates() {
+        let path = temp_path("store");
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+
+        let store = SettingsStore::open(path.clone()).unwrap();
+        assert_eq!(Settings::load(&path).unwrap(), Settings::default());
+
+        let mut rx = store.subscribe();
+        store.update(|s| s.notifications.charged = Some(80)).unwrap();
+        assert!(rx.has_changed().unwrap());
+        rx.mark_unchanged();
+        assert_eq!(Settings::load(&path).unwrap().notifications.charged, Some(80));
+
+        // 校验失败时不写文件，也不通知订阅者
+        assert!(store.update(|s| s.notifications.low = Some(150)).is_err());
+        assert!(!rx.has_changed().unwrap());
+        assert_eq!(SettingsStore::open(path.clone()).unwrap().get(), store.get());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn in_memory_store_keeps_updates_without_a_file() {
+        let store = SettingsStore::in_memory(Settings::default());
+        assert_eq!(store.path(), None);
+        store.update(|s| s.icon.glyph_number = true).unwrap();
+        assert!(store.get().icon.glyph_number);
+        store.reload().unwrap();
+        assert!(store.get().icon.glyph_number);
+        assert!(store.update(|s| s.icon.full_threshold = 101).is_err());
+        assert!(Arc::new(store).watch(|_| {}).is_err());
+    }
+}
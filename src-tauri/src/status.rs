@@ -0,0 +1,70 @@
+use serde::Serialize;
+
+use crate::{
+    aggregation::Aggregation,
+    battery_source::{BatteryReading, ChargeState, SourceUpdate},
+};
+
+/// 对外提供的电池状态，命令行 `status --json` 与 HTTP `/status` 使用同一格式
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusReport {
+    /// 按设置的汇总方式得到的总体读数，没有电池时为 null
+    pub headline: Option<ReadingReport>,
+    pub batteries: Vec<ReadingReport>,
+    /// 数据源无法读取时的错误信息
+    pub error: Option<String>,
+}
+
+/// 单块电池的读数，时间以秒表示
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadingReport {
+    pub name: String,
+    pub percentage: u32,
+    pub state: ChargeState,
+    pub energy: Option<f32>,
+    pub energy_full: Option<f32>,
+    pub energy_rate: Option<f32>,
+    pub voltage: Option<f32>,
+    pub temperature: Option<f32>,
+    pub time_to_empty_secs: Option<u64>,
+    pub time_to_full_secs: Option<u64>,
+}
+
+impl From<&BatteryReading> for ReadingReport {
+    fn from(reading: &BatteryReading) -> Self {
+        Self {
+            name: reading.name.clone(),
+            percentage: reading.percentage,
+            state: reading.state,
+            energy: reading.energy,
+            energy_full: reading.energy_full,
+            energy_rate: reading.energy_rate,
+            voltage: reading.voltage,
+            temperature: reading.temperature,
+            time_to_empty_secs: reading.time_to_empty.map(|d| d.as_secs()),
+            time_to_full_secs: reading.time_to_full.map(|d| d.as_secs()),
+        }
+    }
+}
+
+impl StatusReport {
+    pub fn new(batteries: &[BatteryReading], aggregation: Aggregation) -> Self {
+        Self {
+            headline: aggregation.headline(batteries).as_ref().map(ReadingReport::from),
+            batteries: batteries.iter().map(ReadingReport::from).collect(),
+            error: None,
+        }
+    }
+
+    /// 由监视器的最新消息生成，尚未读到数据时电池列表为空
+    pub fn from_update(update: Option<&SourceUpdate>, aggregation: Aggregation) -> Self {
+        match update {
+            Some(SourceUpdate::Readings(batteries)) => Self::new(batteries, aggregation),
+            Some(SourceUpdate::Error(message)) => Self {
+                error: Some(message.clone()),
+                ..Self::new(&[], aggregation)
+            },
+            None => Self::new(&[], aggregation),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::{thread, time::Duration};
+use tokio::sync::watch;
+
+use crate::icon_style::Appearance;
+
+/// 在后台线程跟踪系统外观，变化时通过返回的通道通知。
+/// 通道在判断出系统外观之前为浅色
+pub fn spawn_appearance_watcher() -> watch::Receiver<Appearance> {
+    let (tx, rx) = watch::channel(Appearance::default());
+    thread::spawn(move || platform::watch(&tx));
+    rx
+}
+
+/// 只有外观确实变化时才通知订阅者
+fn publish(tx: &watch::Sender<Appearance>, appearance: Appearance) {
+    tx.send_if_modified(|current| {
+        if *current == appearance {
+            return false;
+        }
+        *current = appearance;
+        true
+    });
+}
+
+/// Linux 上通过 XDG 桌面门户的 SettingChanged 信号得知外观变化
+#[cfg(target_os = "linux")]
+mod platform {
+    use anyhow::{Context, Result};
+    use zbus::{
+        blocking::{Connection, MessageIterator},
+        message::Type,
+        zvariant::{OwnedValue, Value},
+        MatchRule,
+    };
+
+    use super::*;
+
+    const SERVICE: &str = "org.freedesktop.portal.Desktop";
+    const PATH: &str = "/org/freedesktop/portal/desktop";
+    const INTERFACE: &str = "org.freedesktop.portal.Settings";
+    const NAMESPACE: &str = "org.freedesktop.appearance";
+    const KEY: &str = "color-scheme";
+
+    /// 门户不可用或连接断开后重新连接的间隔，开机自启时门户可能比本程序晚启动
+    const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+    pub fn watch(tx: &watch::Sender<Appearance>) {
+        let mut last_error = None;
+        while !tx.is_closed() {
+            if let Err(e) = follow(tx) {
+                // 没有门户的桌面上每次重试都会失败，相同的错误只打印一次
+                let message = format!("{:#}", e);
+                if last_error.as_ref() != Some(&message) {
+                    eprintln!("{}, retrying in {}s", message, RETRY_INTERVAL.as_secs());
+                    last_error = Some(message);
+                }
+            }
+            thread::sleep(RETRY_INTERVAL);
+        }
+    }
+
+    /// 读取当前外观，然后逐个处理 SettingChanged 信号，直到连接断开
+    fn follow(tx: &watch::Sender<Appearance>) -> Result<()> {
+        let connection = Connection::session().context("Failed to connect to the session bus")?;
+        // 先订阅再读取，避免错过两者之间的变化
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .path(PATH)
+            .and_then(|builder| builder.interface(INTERFACE))
+            .and_then(|builder| builder.member("SettingChanged"))
+            .map(|builder| builder.build());
+        let messages = rule
+            .and_then(|rule| MessageIterator::for_match_rule(rule, &connection, None))
+            .context("Failed to subscribe to desktop portal settings")?;
+        publish(tx, read(&connection)?);
+
+        for message in messages {
+            let message = message.context("Lost the connection to the session bus")?;
+            let Ok((namespace, key, value)) = message.body().deserialize::<(String, String, OwnedValue)>() else {
+                continue;
+            };
+            if namespace == NAMESPACE && key == KEY {
+                publish(tx, appearance(&value));
+            }
+            if tx.is_closed() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn read(connection: &Connection) -> Result<Appearance> {
+        let call = |method| connection.call_method(Some(SERVICE), PATH, Some(INTERFACE), method, &(NAMESPACE, KEY));
+        // ReadOne 从门户的第 2 版开始提供，旧版本只有已弃用的 Read
+        let reply = call("ReadOne")
+            .or_else(|_| call("Read"))
+            .context("Failed to read the color scheme from the desktop portal")?;
+        let value: OwnedValue = reply
+            .body()
+            .deserialize()
+            .context("Invalid color scheme from the desktop portal")?;
+        Ok(appearance(&value))
+    }
+
+    /// color-scheme 为 1 表示偏好深色，0（无偏好）和 2（偏好浅色）视为浅色。
+    /// Read 的结果比 ReadOne 多包一层变体
+    fn appearance(value: &Value<'_>) -> Appearance {
+        match value {
+            Value::Value(inner) => appearance(inner),
+            Value::U32(1) => Appearance::Dark,
+            _ => Appearance::Light,
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn maps_color_scheme_to_appearance() {
+            let cases = [
+                (Value::U32(0), Appearance::Light),
+                (Value::U32(1), Appearance::Dark),
+                (Value::U32(2), Appearance::Light),
+                (Value::Value(Box::new(Value::U32(1))), Appearance::Dark),
+                (Value::Value(Box::new(Value::U32(2))), Appearance::Light),
+                (Value::from("dark"), Appearance::Light),
+            ];
+            for (value, expected) in cases {
+                assert_eq!(appearance(&value), expected, "{:?}", value);
+            }
+        }
+    }
+}
+
+/// 其他平台上托盘没有窗口可以接收主题变化事件，只能定期查询
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use super::*;
+
+    const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+    pub fn watch(tx: &watch::Sender<Appearance>) {
+        while !tx.is_closed() {
+            publish(tx, detect());
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// 无法判断时视为浅色
+    fn detect() -> Appearance {
+        match dark_light::detect() {
+            dark_light::Mode::Dark => Appearance::Dark,
+            dark_light::Mode::Light | dark_light::Mode::Default => Appearance::Light,
+        }
+    }
+}
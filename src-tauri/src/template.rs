@@ -0,0 +1,311 @@
+use std::{fmt, str::FromStr};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    battery_source::{BatteryReading, ChargeState},
+    estimator::TimeLeft,
+};
+
+/// 模板中可用的值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// 电量百分比数字
+    Pct,
+    /// 电池名称，外设为型号
+    Name,
+    /// 状态名称，如 `charging`
+    State,
+    /// 状态描述，如 `Charging: 42%`
+    Status,
+    /// 剩余时间，如 `2h 13m remaining`
+    TimeLeft,
+    /// 简短的剩余时间，如 `2h13m`
+    Time,
+    /// 充放电功率，如 `12.3W`
+    Watts,
+    /// 不带单位的紧凑功率，如 `9.8`、`23`，适合放在图标上
+    Power,
+    /// 电压，如 `12.1V`
+    Voltage,
+    /// 温度，如 `31.5°C`
+    Temp,
+    /// 功率、电压、温度中可用的部分，如 `12.3W · 12.1V · 31.5°C`
+    Details,
+}
+
+impl Field {
+    const ALL: [(&'static str, Field); 11] = [
+        ("pct", Field::Pct),
+        ("name", Field::Name),
+        ("state", Field::State),
+        ("status", Field::Status),
+        ("time_left", Field::TimeLeft),
+        ("time", Field::Time),
+        ("watts", Field::Watts),
+        ("power", Field::Power),
+        ("voltage", Field::Voltage),
+        ("temp", Field::Temp),
+        ("details", Field::Details),
+    ];
+}
+
+/// 只在特定状态下输出的文字
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Condition {
+    State(ChargeState),
+    /// 接通电源，包括充电、充满和暂停充电
+    PluggedIn,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Field(Field),
+    When(Condition, String),
+}
+
+/// 文字模板，如 `{pct}{charging:*}`。`{字段}` 替换为对应的值，数据缺失时为空；
+/// `{状态:文字}` 只在对应状态下输出，状态可以是 charging、discharging、full、empty、
+/// not-charging、unknown 或 plugged；`{{`、`}}` 表示花括号本身
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Template {
+    source: String,
+    parts: Vec<Part>,
+}
+
+/// 渲染模板所需的数据
+pub struct TemplateContext<'a> {
+    pub reading: &'a BatteryReading,
+    pub time_left: Option<TimeLeft>,
+}
+
+impl Template {
+    pub fn render(&self, context: &TemplateContext) -> String {
+        let reading = context.reading;
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => output.push_str(text),
+                Part::Field(field) => output.push_str(&field_value(*field, context)),
+                Part::When(condition, text) => {
+                    let matched = match condition {
+                        Condition::State(state) => reading.state == *state,
+                        Condition::PluggedIn => reading.state.is_plugged_in(),
+                    };
+                    if matched {
+                        output.push_str(text);
+                    }
+                }
+            }
+        }
+        output
+    }
+}
+
+fn field_value(field: Field, context: &TemplateContext) -> String {
+    let reading = context.reading;
+    match field {
+        Field::Pct => reading.percentage.to_string(),
+        Field::Name => reading.label().to_string(),
+        Field::State => reading.state.as_str().to_string(),
+        Field::Status => describe_reading(reading),
+        Field::TimeLeft => context.time_left.map(|t| t.to_string()).unwrap_or_default(),
+        Field::Time => context.time_left.map(|t| t.short()).unwrap_or_default(),
+        Field::Watts => reading.energy_rate.map(|w| format!("{:.1}W", w)).unwrap_or_default(),
+        Field::Power => reading.energy_rate.map(compact_watts).unwrap_or_default(),
+        Field::Voltage => reading.voltage.map(|v| format!("{:.1}V", v)).unwrap_or_default(),
+        Field::Temp => reading.temperature.map(|t| format!("{:.1}°C", t)).unwrap_or_default(),
+        Field::Details => [Field::Watts, Field::Voltage, Field::Temp]
+            .into_iter()
+            .map(|field| field_value(field, context))
+            .filter(|value| !value.is_empty())
+            .collect::<Vec<_>>()
+            .join(" · "),
+    }
+}
+
+/// 不超过三个字符的功率数值：10W 以下保留一位小数
+fn compact_watts(watts: f32) -> String {
+    if watts < 9.95 {
+        format!("{:.1}", watts)
+    } else {
+        format!("{:.0}", watts.min(999.0))
+    }
+}
+
+/// 单条电池状态描述，如 `Charging: 42%`、`Full`
+pub fn describe_reading(reading: &BatteryReading) -> String {
+    match reading.state {
+        ChargeState::Full | ChargeState::Empty => reading.state.label().to_string(),
+        state => format!("{}: {}%", state.label(), reading.percentage),
+    }
+}
+
+impl FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => bail!("Unclosed placeholder in template: {}", s),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(parse_placeholder(&placeholder)?);
+                }
+                '}' => bail!("Unmatched '}}' in template: {} (use '}}}}' for a literal brace)", s),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Self { source: s.to_string(), parts })
+    }
+}
+
+fn parse_placeholder(placeholder: &str) -> Result<Part> {
+    if let Some((condition, text)) = placeholder.split_once(':') {
+        let condition = match condition {
+            "plugged" => Condition::PluggedIn,
+            _ => match condition.parse() {
+                Ok(state) => Condition::State(state),
+                Err(_) => bail!("Unknown condition in template: {{{}}}", placeholder),
+            },
+        };
+        return Ok(Part::When(condition, text.to_string()));
+    }
+    match Field::ALL.iter().find(|(name, _)| *name == placeholder) {
+        Some(&(_, field)) => Ok(Part::Field(field)),
+        None => bail!("Unknown placeholder in template: {{{}}}", placeholder),
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl TryFrom<String> for Template {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Template> for String {
+    fn from(template: Template) -> Self {
+        template.source
+    }
+}
+
+/// 托盘提示的格式，渲染后的空行会被省略
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TooltipConfig {
+    /// 汇总电量的描述
+    pub summary: Template,
+    /// 多块电池时每块电池一行
+    pub battery: Template,
+}
+
+impl Default for TooltipConfig {
+    fn default() -> Self {
+        Self {
+            summary: "{status}\n{time_left}\n{details}".parse().unwrap(),
+            battery: "{name}: {status}".parse().unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn render(template: &str, reading: &BatteryReading) -> String {
+        let template: Template = template.parse().unwrap();
+        template.render(&TemplateContext { reading, time_left: None })
+    }
+
+    #[test]
+    fn parses_escaped_braces() {
+        let reading = BatteryReading::new("BAT0", 42, ChargeState::Discharging);
+        assert_eq!(render("{{{pct}}}", &reading), "{42}");
+        assert_eq!(render("}}{{", &reading), "}{");
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for template in ["{pct", "pct}", "{pct}}", "{watt}", "{sideways:x}", "{}"] {
+            assert!(template.parse::<Template>().is_err(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn normalizes_condition_states() {
+        for template in ["{not-charging:~}", "{not_charging:~}", "{Not charging:~}"] {
+            let template: Template = template.parse().unwrap();
+            let expected = Part::When(Condition::State(ChargeState::NotCharging), "~".to_string());
+            assert_eq!(template.parts, [expected]);
+        }
+    }
+
+    #[test]
+    fn renders_conditions() {
+        let template = "{pct}{charging:+}{plugged:*}";
+        assert_eq!(render(template, &BatteryReading::new("BAT0", 42, ChargeState::Charging)), "42+*");
+        assert_eq!(render(template, &BatteryReading::new("BAT0", 80, ChargeState::NotCharging)), "80*");
+        assert_eq!(render(template, &BatteryReading::new("BAT0", 30, ChargeState::Discharging)), "30");
+    }
+
+    #[test]
+    fn renders_missing_fields_as_empty() {
+        let reading = BatteryReading::new("BAT0", 42, ChargeState::Discharging);
+        assert_eq!(render("[{time}|{watts}|{voltage}|{temp}|{details}]", &reading), "[||||]");
+
+        let reading = BatteryReading {
+            energy_rate: Some(12.34),
+            temperature: Some(31.5),
+            ..reading
+        };
+        let template: Template = "{details} {power} {time_left}".parse().unwrap();
+        let context = TemplateContext {
+            reading: &reading,
+            time_left: Some(TimeLeft::ToEmpty(Duration::from_secs(45 * 60))),
+        };
+        assert_eq!(template.render(&context), "12.3W · 31.5°C 12 45m remaining");
+    }
+
+    #[test]
+    fn keeps_the_source_text() {
+        let template: Template = "{name}: {{{status}}}".parse().unwrap();
+        assert_eq!(template.to_string(), "{name}: {{{status}}}");
+    }
+}
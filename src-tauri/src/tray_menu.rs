@@ -0,0 +1,296 @@
+use std::sync::Arc;
+use tauri::{
+    menu::{CheckMenuItem, IsMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu},
+    AppHandle, Manager, Wry,
+};
+use tauri_plugin_autostart::ManagerExt;
+use tauri_plugin_opener::OpenerExt;
+
+use crate::{
+    aggregation::Aggregation,
+    fonts::{BundledFont, FontChoice},
+    icon_style::IconMode,
+    peripherals::Peripherals,
+    settings::{Settings, SettingsStore},
+    windows,
+};
+
+pub const TRAY_ID: &str = "tray_id";
+
+pub fn init_menu(app: &AppHandle) -> Result<Menu<Wry>, tauri::Error> {
+    let autostart_status = app
+        .autolaunch()
+        .is_enabled()
+        .unwrap_or(false);
+    let autostart_item = MenuItem::with_id(
+        app,
+        "autostart",
+        format!("{} autostart", if autostart_status { "Disable" } else { "Enable" }),
+        true,
+        None::<&str>,
+    )?;
+    let aggregation_menu = init_aggregation_menu(app)?;
+    let display_menu = init_display_menu(app)?;
+    let peripheral_menu = init_peripheral_menu(app)?;
+    let history_item = MenuItem::with_id(
+        app,
+        "history",
+        "History…",
+        true,
+        None::<&str>,
+    )?;
+    let health_item = MenuItem::with_id(
+        app,
+        "health",
+        "Battery health…",
+        true,
+        None::<&str>,
+    )?;
+    // 找不到配置目录时设置只在内存中，没有文件可以编辑
+    let has_settings_file = app.state::<Arc<SettingsStore>>().path().is_some();
+    let settings_item = MenuItem::with_id(
+        app,
+        "settings",
+        "Edit settings…",
+        has_settings_file,
+        None::<&str>,
+    )?;
+    let quit_item = MenuItem::with_id(
+        app,
+        "quit",
+        "Quit",
+        true,
+        None::<&str>
+    )?;
+    Menu::with_items(
+        app,
+        &[&display_menu, &aggregation_menu, &peripheral_menu, &history_item, &health_item, &settings_item, &autostart_item, &quit_item],
+    )
+}
+
+/// 多电池汇总方式的子菜单，勾选当前模式
+fn init_aggregation_menu(app: &AppHandle) -> Result<Submenu<Wry>, tauri::Error> {
+    let current = app.state::<Arc<SettingsStore>>().get().aggregation;
+    let items = Aggregation::ALL
+        .into_iter()
+        .map(|aggregation| {
+            CheckMenuItem::with_id(
+                app,
+                format!("aggregation:{}", aggregation.as_str()),
+                aggregation.label(),
+                true,
+                aggregation == current,
+                None::<&str>,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let items: Vec<&dyn IsMenuItem<Wry>> = items.iter().map(|item| item as &dyn IsMenuItem<Wry>).collect();
+    Submenu::with_items(app, "Batteries", true, &items)
+}
+
+/// 外设子菜单：列出外设及其电量，勾选的外设代替电池显示在托盘图标上。
+/// 固定的外设不在线时也列出，以便取消固定
+fn init_peripheral_menu(app: &AppHandle) -> Result<Submenu<Wry>, tauri::Error> {
+    let pinned = app.state::<Arc<SettingsStore>>().get().peripherals.pinned;
+    let peripherals = app.state::<Peripherals>().readings();
+    let mut items = peripherals
+        .iter()
+        .map(|device| {
+            CheckMenuItem::with_id(
+                app,
+                format!("pin:{}", device.name),
+                format!("{}: {}%", device.label(), device.percentage),
+                true,
+                pinned.as_ref() == Some(&device.name),
+                None::<&str>,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(name) = pinned.filter(|name| !peripherals.iter().any(|device| &device.name == name)) {
+        items.push(CheckMenuItem::with_id(
+            app,
+            format!("pin:{}", name),
+            format!("{}: offline", name),
+            true,
+            true,
+            None::<&str>,
+        )?);
+    }
+    if items.is_empty() {
+        items.push(CheckMenuItem::new(app, "No devices", false, false, None::<&str>)?);
+    }
+    let items: Vec<&dyn IsMenuItem<Wry>> = items.iter().map(|item| item as &dyn IsMenuItem<Wry>).collect();
+    Submenu::with_items(app, "Devices", true, &items)
+}
+
+/// 图标绘制方式的子菜单
+fn init_display_menu(app: &AppHandle) -> Result<Submenu<Wry>, tauri::Error> {
+    let style = app.state::<Arc<SettingsStore>>().get().icon;
+    let mode_items = IconMode::ALL
+        .into_iter()
+        .map(|mode| {
+            CheckMenuItem::with_id(
+                app,
+                format!("mode:{}", mode.as_str()),
+                mode.label(),
+                true,
+                mode == style.mode,
+                None::<&str>,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let separator = PredefinedMenuItem::separator(app)?;
+    let glyph_number_item = CheckMenuItem::with_id(
+        app,
+        "glyph-number",
+        "Show number on glyph",
+        style.mode == IconMode::Glyph,
+        style.glyph_number,
+        None::<&str>,
+    )?;
+
+    let font_menu = init_font_menu(app, &style.font)?;
+    let per_device_item = CheckMenuItem::with_id(
+        app,
+        "per-device",
+        "One icon per device",
+        true,
+        app.state::<Arc<SettingsStore>>().get().tray.per_device,
+        None::<&str>,
+    )?;
+
+    let mut items: Vec<&dyn IsMenuItem<Wry>> = mode_items.iter().map(|item| item as &dyn IsMenuItem<Wry>).collect();
+    items.push(&separator);
+    items.push(&glyph_number_item);
+    items.push(&font_menu);
+    items.push(&per_device_item);
+    Submenu::with_items(app, "Display", true, &items)
+}
+
+/// 字体子菜单：列出内置字体，配置了其他字体时额外显示一项不可点击的勾选项
+fn init_font_menu(app: &AppHandle, current: &FontChoice) -> Result<Submenu<Wry>, tauri::Error> {
+    let mut items = BundledFont::ALL
+        .into_iter()
+        .map(|font| {
+            CheckMenuItem::with_id(
+                app,
+                format!("font:{}", font.as_str()),
+                font.label(),
+                true,
+                *current == FontChoice::Bundled(font),
+                None::<&str>,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    match current {
+        FontChoice::Bundled(_) => {}
+        FontChoice::File(path) => {
+            let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+            items.push(CheckMenuItem::new(app, format!("Custom: {}", name), false, true, None::<&str>)?);
+        }
+        FontChoice::Family(family) => {
+            items.push(CheckMenuItem::new(app, format!("Custom: {}", family), false, true, None::<&str>)?);
+        }
+    }
+    let items: Vec<&dyn IsMenuItem<Wry>> = items.iter().map(|item| item as &dyn IsMenuItem<Wry>).collect();
+    Submenu::with_items(app, "Font", true, &items)
+}
+
+/// 设置变化是否影响菜单的勾选状态
+pub fn menu_affected(old: &Settings, new: &Settings) -> bool {
+    old.aggregation != new.aggregation
+        || old.icon.mode != new.icon.mode
+        || old.icon.glyph_number != new.icon.glyph_number
+        || old.icon.font != new.icon.font
+        || old.peripherals.pinned != new.peripherals.pinned
+        || old.tray.per_device != new.tray.per_device
+}
+
+/// 修改设置并写回文件。即使设置没有变化也刷新菜单，恢复被点击项自动切换的勾选状态
+fn update_settings(app: &AppHandle, f: impl FnOnce(&mut Settings)) {
+    let store = app.state::<Arc<SettingsStore>>();
+    if let Err(e) = store.update(f) {
+        eprintln!("Failed to save settings: {:#}", e);
+    }
+    refresh_menu(app);
+}
+
+/// 重新生成托盘菜单以反映最新状态
+pub fn refresh_menu(app: &AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        eprintln!("Failed to refresh tray menu: tray not found");
+        return;
+    };
+    let menu = match init_menu(app) {
+        Ok(menu) => menu,
+        Err(e) => {
+            eprintln!("Failed to build tray menu: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = tray.set_menu(Some(menu)) {
+        eprintln!("Failed to update tray menu: {}", e);
+    }
+}
+
+/// 处理托盘菜单点击
+pub fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    match event.id.as_ref() {
+        "quit" => {
+            println!("User clicked quit");
+            app.exit(0);
+        }
+        "autostart" => {
+            let autostart = app.autolaunch();
+            let enabled = autostart.is_enabled().unwrap_or(false);
+            if enabled {
+                let _ = autostart.disable();
+            } else {
+                let _ = autostart.enable();
+            }
+            refresh_menu(app);
+        }
+        "history" => {
+            if let Err(e) = windows::show_history(app) {
+                eprintln!("Failed to open history window: {}", e);
+            }
+        }
+        "health" => {
+            if let Err(e) = windows::show_health(app) {
+                eprintln!("Failed to open health window: {}", e);
+            }
+        }
+        "settings" => {
+            let store = app.state::<Arc<SettingsStore>>();
+            let Some(path) = store.path() else {
+                return;
+            };
+            if let Err(e) = app.opener().open_path(path.to_string_lossy(), None::<&str>) {
+                eprintln!("Failed to open settings file: {}", e);
+            }
+        }
+        "glyph-number" => {
+            update_settings(app, |settings| settings.icon.glyph_number = !settings.icon.glyph_number);
+        }
+        "per-device" => {
+            update_settings(app, |settings| settings.tray.per_device = !settings.tray.per_device);
+        }
+        other => {
+            if let Some(Ok(aggregation)) = other.strip_prefix("aggregation:").map(str::parse::<Aggregation>) {
+                update_settings(app, |settings| settings.aggregation = aggregation);
+            } else if let Some(Ok(mode)) = other.strip_prefix("mode:").map(str::parse::<IconMode>) {
+                update_settings(app, |settings| settings.icon.mode = mode);
+            } else if let Some(Ok(font)) = other.strip_prefix("font:").map(str::parse::<BundledFont>) {
+                update_settings(app, |settings| settings.icon.font = FontChoice::Bundled(font));
+            } else if let Some(name) = other.strip_prefix("pin:") {
+                // 再次点击已固定的外设时取消固定
+                update_settings(app, |settings| {
+                    let pinned = &mut settings.peripherals.pinned;
+                    *pinned = if pinned.as_deref() == Some(name) { None } else { Some(name.to_string()) };
+                });
+            } else {
+                println!("Unhandled menu item: {:?}", other);
+            }
+        }
+    }
+}
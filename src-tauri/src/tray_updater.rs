@@ -0,0 +1,296 @@
+use std::sync::Arc;
+use tauri::{async_runtime, image::Image, tray::TrayIcon, AppHandle, Manager};
+use tokio::{
+    sync::{watch, Mutex},
+    time::{self, Instant, Interval, MissedTickBehavior},
+};
+
+use crate::{
+    aggregation::Aggregation,
+    battery_icon_generator::BatteryIconGenerator,
+    battery_source::{BatteryReading, ChargeState, SourceUpdate},
+    estimator::{TimeEstimator, TimeLeft},
+    history::{HistoryConfig, HistoryStore},
+    icon_style::Appearance,
+    notifier::{self, AlertTracker},
+    peripherals::Peripherals,
+    settings::Settings,
+    template::TemplateContext,
+    tray_menu,
+};
+
+/// 托盘更新任务持有的状态
+struct TrayUpdater {
+    app: AppHandle,
+    tray: Arc<Mutex<TrayIcon>>,
+    icon_generator: BatteryIconGenerator,
+    estimator: TimeEstimator,
+    alerts: AlertTracker,
+    settings: Settings,
+    appearance: Appearance,
+    /// 上次刷新菜单时各外设的显示名、电量和名称
+    menu_peripherals: Vec<(String, u32, String)>,
+}
+
+/// 启动异步任务监听电池更新并修改托盘图标，设置变化时用最近一次数据重绘。
+/// 外设变化时刷新菜单中的电量；读数不变时按 history.min_interval 定期写入历史记录
+pub fn spawn_tray_updater(
+    app: AppHandle,
+    tray: Arc<Mutex<TrayIcon>>,
+    mut rx: watch::Receiver<Option<SourceUpdate>>,
+    mut peripherals_rx: watch::Receiver<Option<SourceUpdate>>,
+    mut settings_rx: watch::Receiver<Settings>,
+    mut appearance_rx: watch::Receiver<Appearance>,
+) {
+    async_runtime::spawn(async move {
+        let mut updater = TrayUpdater {
+            app,
+            tray,
+            icon_generator: BatteryIconGenerator::default(),
+            estimator: TimeEstimator::default(),
+            alerts: AlertTracker::default(),
+            settings: Settings::default(),
+            appearance: *appearance_rx.borrow_and_update(),
+            menu_peripherals: Vec::new(),
+        };
+        updater.apply_settings(settings_rx.borrow_and_update().clone());
+        let mut heartbeat = history_heartbeat(&updater.settings.history);
+
+        loop {
+            tokio::select! {
+                changed = rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    // 只有读数本身更新时才计入历史、剩余时间估算和电量提醒，其他事件只重绘
+                    if let Some(update) = &*rx.borrow_and_update() {
+                        updater.record(update);
+                    }
+                }
+                changed = peripherals_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    peripherals_rx.mark_unchanged();
+                    updater.refresh_peripheral_menu();
+                }
+                changed = settings_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let min_interval = updater.settings.history.min_interval;
+                    updater.apply_settings(settings_rx.borrow_and_update().clone());
+                    if updater.settings.history.min_interval != min_interval {
+                        heartbeat = history_heartbeat(&updater.settings.history);
+                    }
+                }
+                changed = appearance_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    updater.appearance = *appearance_rx.borrow_and_update();
+                    updater.apply_theme();
+                }
+                _ = heartbeat.tick() => {
+                    // 读数没有变化，只补写历史记录，不必重绘
+                    if let Some(SourceUpdate::Readings(readings)) = &*rx.borrow() {
+                        updater.record_history(readings);
+                    }
+                    continue;
+                }
+            }
+
+            // 收到第一条消息前不更新托盘
+            let latest = rx.borrow().clone();
+            if let Some(update) = &latest {
+                updater.update(update).await;
+            }
+        }
+    });
+}
+
+/// 电量不变时补写历史记录的定时器，第一次在一个间隔后触发
+fn history_heartbeat(config: &HistoryConfig) -> Interval {
+    let mut interval = time::interval_at(Instant::now() + config.min_interval, config.min_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval
+}
+
+/// 根据主显示器的缩放比例决定图标尺寸
+pub fn tray_size(app: &AppHandle) -> u32 {
+    let scale_factor = match app.primary_monitor() {
+        Ok(Some(monitor)) => monitor.scale_factor(),
+        _ => 1.0,
+    };
+    BatteryIconGenerator::tray_size_for_scale(scale_factor)
+}
+
+impl TrayUpdater {
+    /// 外设列表、显示名或电量变化时才重建菜单，外设每次轮询都会发来读数
+    fn refresh_peripheral_menu(&mut self) {
+        let peripherals: Vec<_> = self
+            .app
+            .state::<Peripherals>()
+            .readings()
+            .into_iter()
+            .map(|device| (device.label().to_string(), device.percentage, device.name))
+            .collect();
+        if peripherals != self.menu_peripherals {
+            self.menu_peripherals = peripherals;
+            tray_menu::refresh_menu(&self.app);
+        }
+    }
+
+    /// 应用新的设置，必要时同步菜单勾选状态
+    fn apply_settings(&mut self, settings: Settings) {
+        let menu_affected = tray_menu::menu_affected(&self.settings, &settings);
+        if let Err(e) = self.icon_generator.set_style(settings.icon.clone()) {
+            eprintln!("{:#}", e);
+            notifier::show_message(&self.app, "Font unavailable", &format!("{:#}", e));
+        }
+        let size = settings.icon.size.unwrap_or_else(|| tray_size(&self.app));
+        self.icon_generator.set_size(size);
+        self.settings = settings;
+        self.apply_theme();
+        if menu_affected {
+            tray_menu::refresh_menu(&self.app);
+        }
+    }
+
+    /// 处理监视器的新消息：写入历史记录，计入剩余时间估算并检查电量提醒
+    fn record(&mut self, update: &SourceUpdate) {
+        let SourceUpdate::Readings(batteries) = update else {
+            return;
+        };
+        self.record_history(batteries);
+        let Some(headline) = self.settings.aggregation.headline(batteries) else {
+            return;
+        };
+        self.estimator.record(&headline);
+        if let Some(alert) = self.alerts.check(&self.settings.notifications, &headline) {
+            notifier::show_alert(&self.app, alert, headline.percentage);
+        }
+    }
+
+    /// 把读数写入历史记录。写文件和清理过期记录可能阻塞，放到阻塞线程池中执行；
+    /// 失败时只输出日志，不影响托盘更新
+    fn record_history(&self, readings: &[BatteryReading]) {
+        if !self.settings.history.enabled {
+            return;
+        }
+        let history = Arc::clone(&self.app.state::<Arc<HistoryStore>>());
+        let config = self.settings.history.clone();
+        let readings = readings.to_vec();
+        async_runtime::spawn_blocking(move || {
+            if let Err(e) = history.record(&config, &readings) {
+                eprintln!("Failed to record battery history: {:#}", e);
+            }
+        });
+    }
+
+    /// 按设置和系统外观选择配色
+    fn apply_theme(&mut self) {
+        let theme = self.settings.icon.resolve_theme(self.appearance);
+        self.icon_generator.set_theme(theme);
+    }
+
+    /// 按监视器的最新消息重绘托盘：没有电池时显示 AC，数据源出错时显示错误图标。
+    /// 固定了在线的外设时，图标改为显示该外设，提示的第一行为外设的电量
+    async fn update(&self, update: &SourceUpdate) {
+        let status = match update {
+            SourceUpdate::Readings(batteries) if batteries.is_empty() => Some((
+                self.icon_generator.generate_status_icon("AC", false),
+                "No battery, running on AC power".to_string(),
+            )),
+            SourceUpdate::Readings(batteries) => self.battery_status(batteries).await,
+            SourceUpdate::Error(message) => Some((
+                self.icon_generator.generate_status_icon("!", true),
+                format!("Battery unavailable, retrying\n{}", message),
+            )),
+        };
+        let Some((icon, tooltip)) = status else {
+            return;
+        };
+
+        match self.pinned_status().await {
+            Some((pinned_icon, line)) => self.set_tray(pinned_icon, &format!("{}\n{}", line, tooltip)).await,
+            None => self.set_tray(icon, &tooltip).await,
+        }
+    }
+
+    /// 固定在图标上的外设的图标与提示，没有固定或该外设不在线时返回 None
+    async fn pinned_status(&self) -> Option<(Image<'static>, String)> {
+        let device = self.app.state::<Peripherals>().pinned(&self.settings.peripherals)?;
+        let context = TemplateContext {
+            reading: &device,
+            time_left: TimeLeft::reported(&device),
+        };
+        let text = self.icon_generator.icon_text(std::slice::from_ref(&context));
+        match self.icon_generator.generate_icon(&[(device.percentage, device.state)], &text).await {
+            Ok(icon) => Some((icon, self.settings.tooltip.battery.render(&context))),
+            Err(e) => {
+                eprintln!("Failed to generate tray icon: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// 按汇总方式绘制图标并生成托盘提示
+    async fn battery_status(&self, batteries: &[BatteryReading]) -> Option<(Image<'static>, String)> {
+        let aggregation = self.settings.aggregation;
+        let headline = aggregation.headline(batteries)?;
+        let summary = TemplateContext {
+            reading: &headline,
+            time_left: self.estimator.estimate(&headline),
+        };
+        let contexts: Vec<TemplateContext> = batteries
+            .iter()
+            .map(|reading| TemplateContext {
+                reading,
+                time_left: TimeLeft::reported(reading),
+            })
+            .collect();
+
+        let icon_contexts = match aggregation {
+            Aggregation::PerBattery => &contexts[..],
+            _ => std::slice::from_ref(&summary),
+        };
+        let packs: Vec<(u32, ChargeState)> = icon_contexts
+            .iter()
+            .map(|c| (c.reading.percentage, c.reading.state))
+            .collect();
+        let text = self.icon_generator.icon_text(icon_contexts);
+        let icon = match self.icon_generator.generate_icon(&packs, &text).await {
+            Ok(icon) => icon,
+            Err(e) => {
+                eprintln!("Failed to generate tray icon: {:#}", e);
+                return None;
+            }
+        };
+
+        let tooltip_config = &self.settings.tooltip;
+        let mut lines = vec![tooltip_config.summary.render(&summary)];
+        if batteries.len() > 1 && aggregation != Aggregation::Primary {
+            lines.extend(contexts.iter().map(|c| tooltip_config.battery.render(c)));
+        }
+        // 数据缺失的字段渲染为空，去掉因此产生的空行
+        let tooltip = lines
+            .iter()
+            .flat_map(|text| text.lines())
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some((icon, tooltip))
+    }
+
+    /// 更新托盘图标和提示
+    async fn set_tray(&self, icon: Image<'static>, tooltip: &str) {
+        let tray = self.tray.lock().await;
+        if let Err(e) = tray.set_icon(Some(icon)) {
+            eprintln!("Failed to update tray icon: {}", e);
+        }
+        if let Err(e) = tray.set_tooltip(Some(tooltip)) {
+            eprintln!("Failed to update tray tooltip: {}", e);
+        }
+    }
+}
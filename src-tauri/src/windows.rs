@@ -0,0 +1,29 @@
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder};
+
+/// 窗口标签，与 capabilities 中的窗口名一致
+pub const HISTORY_WINDOW: &str = "history";
+pub const HEALTH_WINDOW: &str = "health";
+
+/// 打开历史图表窗口，已打开时将其置于前台
+pub fn show_history(app: &AppHandle) -> tauri::Result<()> {
+    show_window(app, HISTORY_WINDOW, "history.html", "Battery history", (760.0, 440.0))
+}
+
+/// 打开电池健康窗口
+pub fn show_health(app: &AppHandle) -> tauri::Result<()> {
+    show_window(app, HEALTH_WINDOW, "health.html", "Battery health", (520.0, 460.0))
+}
+
+/// 打开指定窗口，同一窗口只保留一个实例
+fn show_window(app: &AppHandle, label: &str, page: &str, title: &str, (width, height): (f64, f64)) -> tauri::Result<()> {
+    if let Some(window) = app.get_webview_window(label) {
+        window.unminimize()?;
+        return window.set_focus();
+    }
+    WebviewWindowBuilder::new(app, label, WebviewUrl::App(page.into()))
+        .title(title)
+        .inner_size(width, height)
+        .min_inner_size(480.0, 300.0)
+        .build()?;
+    Ok(())
+}